@@ -22,6 +22,20 @@ pub struct HydrationData {
     random_number: f64,
     timestamp: i64,
     component_key: String,
+    /// Set only when constructed via [`HydrationData::from_seed`] /
+    /// [`HydrationData::from_html_seed`]; lets `render_html` embed the seed
+    /// so the client can reconstruct byte-identical values instead of
+    /// rerolling them.
+    seed: Option<u64>,
+}
+
+/// One round of a splitmix64-style mix, used to fork independent-looking
+/// 64-bit values out of a single seed deterministically.
+fn next_from_state(state: &mut u64) -> u64 {
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    state.wrapping_mul(0x2545F4914F6CDD1D)
 }
 
 #[wasm_bindgen]
@@ -55,9 +69,56 @@ impl HydrationData {
             random_number,
             timestamp,
             component_key,
+            seed: None,
         }
     }
-    
+
+    /// The actual fix: derive every value from `seed` via a small in-crate
+    /// splitmix64/xorshift PRNG instead of `Math::random()`/`Date::now()`.
+    /// Called on the server; the client calls [`from_html_seed`](Self::from_html_seed)
+    /// with the same seed (read back out of `render_html`'s
+    /// `data-hydration-seed` attribute) and gets byte-identical values, so
+    /// React no longer sees a hydration mismatch.
+    #[wasm_bindgen]
+    pub fn from_seed(seed: u64) -> HydrationData {
+        let mut state = seed;
+        let uuid_hi = next_from_state(&mut state);
+        let uuid_lo = next_from_state(&mut state);
+        let random_bits = next_from_state(&mut state);
+        let timestamp_bits = next_from_state(&mut state);
+
+        let mut uuid_bytes = [0u8; 16];
+        uuid_bytes[0..8].copy_from_slice(&uuid_hi.to_be_bytes());
+        uuid_bytes[8..16].copy_from_slice(&uuid_lo.to_be_bytes());
+        let session_id = Uuid::from_bytes(uuid_bytes).to_string();
+
+        // Keep the top 53 bits (an f64 mantissa's worth) and scale into
+        // [0, 1), the same way most PRNG-to-float conversions work.
+        let random_number = (random_bits >> 11) as f64 / (1u64 << 53) as f64;
+
+        // Fold down to a plausible millisecond-epoch range so it still
+        // looks like `Date::now()` output.
+        let timestamp = (timestamp_bits % 10_000_000_000_000) as i64;
+
+        let component_key = format!("comp-{}-{}", random_number, timestamp);
+
+        HydrationData {
+            session_id,
+            random_number,
+            timestamp,
+            component_key,
+            seed: Some(seed),
+        }
+    }
+
+    /// Reconstructs the same values as [`from_seed`](Self::from_seed) from
+    /// a seed read off the `data-hydration-seed` attribute `render_html`
+    /// embeds. This is what the client calls during hydration.
+    #[wasm_bindgen]
+    pub fn from_html_seed(seed: u64) -> HydrationData {
+        Self::from_seed(seed)
+    }
+
     /// Returns the session ID (randomly generated UUID)
     /// BUG: This will be different on server vs client!
     #[wasm_bindgen(getter)]
@@ -90,8 +151,16 @@ impl HydrationData {
     /// This is what would be rendered on the server
     #[wasm_bindgen]
     pub fn render_html(&self) -> String {
+        // Only present when built via `from_seed`/`from_html_seed` - the
+        // plain `new()` demo has no seed to hand the client, so it keeps
+        // mismatching on purpose.
+        let seed_attr = match self.seed {
+            Some(seed) => format!(r#" data-hydration-seed="{}""#, seed),
+            None => String::new(),
+        };
+
         format!(
-            r#"<div class="hydration-component" data-session="{}" data-key="{}">
+            r#"<div class="hydration-component" data-session="{}" data-key="{}"{}>
     <h2>Hydration Test Component</h2>
     <p>Session ID: <span id="session-display">{}</span></p>
     <p>Random Number: <span id="random-display">{:.10}</span></p>
@@ -101,6 +170,7 @@ impl HydrationData {
 </div>"#,
             self.session_id,
             self.component_key,
+            seed_attr,
             self.session_id,
             self.random_number,
             self.timestamp
@@ -143,10 +213,10 @@ pub fn create_component_id(prefix: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    #[cfg(target_arch = "wasm32")]
     use super::*;
-    
-    // Note: These tests only work in a WASM environment
+
+    // Note: The `HydrationData::new()`/`generate_random_id` tests below
+    // only work in a WASM environment since they touch `Math`/`Date`.
     // Run with: wasm-pack test --headless --firefox
     
     #[cfg(target_arch = "wasm32")]
@@ -178,4 +248,46 @@ mod tests {
         // It just verifies the module structure compiles
         assert!(true, "Module structure is valid");
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_from_seed_is_deterministic() {
+        let a = HydrationData::from_seed(42);
+        let b = HydrationData::from_seed(42);
+
+        assert_eq!(a.session_id, b.session_id);
+        assert_eq!(a.random_number, b.random_number);
+        assert_eq!(a.timestamp, b.timestamp);
+        assert_eq!(a.component_key, b.component_key);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_from_html_seed_matches_from_seed() {
+        let server = HydrationData::from_seed(1337);
+        let client = HydrationData::from_html_seed(1337);
+
+        assert_eq!(server.session_id, client.session_id);
+        assert_eq!(server.random_number, client.random_number);
+        assert_eq!(server.timestamp, client.timestamp);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_different_seeds_differ() {
+        let a = HydrationData::from_seed(1);
+        let b = HydrationData::from_seed(2);
+
+        assert_ne!(a.session_id, b.session_id);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_render_html_embeds_seed() {
+        let data = HydrationData::from_seed(7);
+        assert!(data.render_html().contains(r#"data-hydration-seed="7""#));
+
+        let buggy = HydrationData::new();
+        assert!(!buggy.render_html().contains("data-hydration-seed"));
+    }
 }
@@ -1,8 +1,14 @@
 /*!
- * Stress Test - Demonstrates the race condition bug reliably
- * 
- * This test runs multiple scenarios to catch the race condition bug.
- * 
+ * Stress Test - Demonstrates that rapid toggles converge on the last click
+ *
+ * This used to hunt for a non-deterministic reordering bug: rapid toggles
+ * could leave local state showing a stale click once a late API response
+ * for an earlier click arrived after a later one had already landed. That
+ * bug is gone - generation versioning (see the module docs) discards any
+ * response that's no longer the latest generation, and ordered per-task
+ * workers apply intents strictly in submission order - so this now asserts
+ * the fix holds instead of gambling on reproducing the old race.
+ *
  * Run with: cargo run --example stress_test
  */
 
@@ -12,17 +18,17 @@ use tokio::time::sleep;
 
 #[tokio::main]
 async fn main() {
-    println!("=== Race Condition Stress Test ===\n");
-    println!("Running multiple test scenarios to demonstrate the bug...\n");
+    println!("=== Rapid Toggle Convergence Test ===\n");
+    println!("Running multiple scenarios to confirm state always converges on the last click...\n");
 
-    let mut bug_detected = 0;
+    let mut mismatches = 0;
     let total_runs = 10;
 
     for run in 1..=total_runs {
         println!("--- Run {}/{} ---", run, total_runs);
-        
+
         let service = TaskToggleService::new(format!("task-{}", run), false);
-        
+
         // Perform rapid toggles
         println!("Executing 5 rapid toggles...");
         for i in 1..=5 {
@@ -31,42 +37,45 @@ async fn main() {
             let state = service.get_local_state().await;
             println!("  After click {}: is_completed = {}", i, state.is_completed);
         }
-        
-        // Expected: is_completed should be true (odd number of toggles)
+
+        // 5 toggles starting from `false` land on `true` - that's the state
+        // the last click committed to, and it should never change once every
+        // in-flight API response has resolved.
         let immediate_state = service.get_local_state().await.is_completed;
         println!("Immediate state (before API responses): {}", immediate_state);
-        
+
         // Wait for all API responses
         println!("Waiting for all API responses...");
         sleep(Duration::from_millis(400)).await;
-        
+
         let final_state = service.get_local_state().await.is_completed;
         println!("Final state (after all API responses): {}", final_state);
-        
-        // Check if bug occurred
+
         if immediate_state != final_state {
-            println!("🐛 BUG DETECTED! State changed from {} to {}", immediate_state, final_state);
-            bug_detected += 1;
+            println!(
+                "❌ MISMATCH! State changed from {} to {} after a stale response landed",
+                immediate_state, final_state
+            );
+            mismatches += 1;
         } else {
-            println!("✓ State remained consistent (bug did not occur this run)");
+            println!("✓ State remained consistent");
         }
-        
+
         println!();
         sleep(Duration::from_millis(100)).await; // Small pause between runs
     }
 
     println!("\n=== Results ===");
     println!("Total runs: {}", total_runs);
-    println!("Bugs detected: {}", bug_detected);
-    println!("Success rate: {}%", (total_runs - bug_detected) * 100 / total_runs);
-    
-    if bug_detected > 0 {
-        println!("\n❌ The race condition bug was detected!");
-        println!("This demonstrates that responses arrive out of order,");
-        println!("causing the UI to flicker to an incorrect state.");
+    println!("Mismatches: {}", mismatches);
+    println!("Success rate: {}%", (total_runs - mismatches) * 100 / total_runs);
+
+    if mismatches > 0 {
+        println!("\n❌ State flickered to a stale value on at least one run!");
+        println!("That's a regression in generation versioning - see `sim_tests` in `src/lib.rs`");
+        println!("for a deterministic reproduction.");
+        std::process::exit(1);
     } else {
-        println!("\n⚠️  Bug not detected in this run.");
-        println!("The bug is non-deterministic - try running again!");
-        println!("With higher network latency, the bug occurs more frequently.");
+        println!("\n✓ State converged on the last click in every run, as expected.");
     }
 }
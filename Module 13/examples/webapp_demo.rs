@@ -1,9 +1,15 @@
 /*!
  * WebApp Demo - Task Toggle Integration
- * 
- * This example demonstrates how the buggy TaskToggleService would be
- * integrated into a real webapp. It shows the race condition bug in action.
- * 
+ *
+ * This example demonstrates how `TaskToggleService` would be integrated
+ * into a real webapp. It used to narrate a UI-flickering race condition
+ * where a late API response for an earlier click could stomp on a later
+ * click's state. That race is fixed: ordered per-task workers apply
+ * clicks strictly in submission order, and generation versioning discards
+ * any response that's no longer the latest generation - so the demo below
+ * instead shows rapid clicks with varied simulated network latency still
+ * converging on the last click every time.
+ *
  * Run with: cargo run --example webapp_demo
  */
 
@@ -15,33 +21,33 @@ use tokio::time::sleep;
 async fn main() {
     println!("=== Task Toggle WebApp Demo ===\n");
     println!("This demo simulates a webapp with a task toggle button.");
-    println!("Watch for the UI flickering bug!\n");
+    println!("Watch rapid clicks converge on the last click despite varying response times.\n");
 
     // Initialize the service (like initializing state in React/Vue)
     let service = TaskToggleService::new("demo-task-1".to_string(), false);
-    
+
     println!("Initial state: {:?}\n", service.get_local_state().await);
 
     // Scenario 1: Normal usage (works fine)
-    println!("--- Scenario 1: Single Click (Works Fine) ---");
+    println!("--- Scenario 1: Single Click ---");
     single_click_demo(&service).await;
-    
+
     println!("\n");
-    
-    // Scenario 2: Rapid clicks (demonstrates the bug)
-    println!("--- Scenario 2: Rapid Triple Click (SHOWS BUG) ---");
+
+    // Scenario 2: Rapid clicks with out-of-order API responses
+    println!("--- Scenario 2: Rapid Triple Click, Out-of-Order Responses ---");
     rapid_click_demo(&service).await;
 }
 
 async fn single_click_demo(service: &TaskToggleService) {
     println!("User clicks toggle button once...");
-    
+
     let state = service.toggle("demo-task-1".to_string()).await.unwrap();
     println!("UI immediately shows: is_completed = {}", state.is_completed);
-    
+
     println!("Waiting for API response...");
     sleep(Duration::from_millis(300)).await;
-    
+
     let final_state = service.get_local_state().await;
     println!("Final state after API: is_completed = {}", final_state.is_completed);
     println!("✓ State is correct!");
@@ -51,49 +57,55 @@ async fn rapid_click_demo(service: &TaskToggleService) {
     // Reset to known state
     let mut current_state = service.get_local_state().await;
     println!("Starting state: is_completed = {}", current_state.is_completed);
-    
-    println!("\nUser rapidly clicks 3 times (like spam-clicking)...");
-    
-    // Click 1
-    println!("  [Time 0ms] Click 1 → Sending request 1");
+
+    println!("\nUser rapidly clicks 3 times (like spam-clicking), each with a different");
+    println!("simulated API delay so responses don't arrive in click order...");
+
+    // Click 1's response is queued to resolve last, well after clicks 2 and 3
+    // - if anything were going to flicker the UI back to a stale state, this
+    // would be it.
+    println!("  [Time 0ms] Click 1 → request queued, resolves in 300ms");
+    service.enqueue_latency(Duration::from_millis(300)).await;
     service.toggle("demo-task-1".to_string()).await.unwrap();
     current_state = service.get_local_state().await;
     println!("    UI shows: is_completed = {}", current_state.is_completed);
     sleep(Duration::from_millis(10)).await;
-    
+
     // Click 2
-    println!("  [Time 10ms] Click 2 → Sending request 2");
+    println!("  [Time 10ms] Click 2 → request queued, resolves in 50ms");
+    service.enqueue_latency(Duration::from_millis(50)).await;
     service.toggle("demo-task-1".to_string()).await.unwrap();
     current_state = service.get_local_state().await;
     println!("    UI shows: is_completed = {}", current_state.is_completed);
     sleep(Duration::from_millis(10)).await;
-    
+
     // Click 3
-    println!("  [Time 20ms] Click 3 → Sending request 3");
+    println!("  [Time 20ms] Click 3 → request queued, resolves in 10ms (this is what user expects!)");
+    service.enqueue_latency(Duration::from_millis(10)).await;
     service.toggle("demo-task-1".to_string()).await.unwrap();
     current_state = service.get_local_state().await;
-    println!("    UI shows: is_completed = {} (this is what user expects!)", current_state.is_completed);
-    
-    println!("\nWaiting for API responses to arrive...");
-    
-    // Check state at intervals to see it change
+    println!("    UI shows: is_completed = {}", current_state.is_completed);
+
+    println!("\nWaiting for API responses to arrive (click 3's response lands first,");
+    println!("click 1's arrives dead last)...");
+
+    // Check state at intervals to see it settle
     for i in 1..=5 {
         sleep(Duration::from_millis(100)).await;
         let state = service.get_local_state().await;
         println!("  [Time {}ms] Current state: is_completed = {}", 20 + (i * 100), state.is_completed);
     }
-    
+
     let final_state = service.get_local_state().await;
-    println!("\n🐛 BUG DETECTED!");
-    println!("Expected final state: is_completed = true (from click 3)");
+    println!("\nExpected final state: is_completed = true (from click 3)");
     println!("Actual final state: is_completed = {}", final_state.is_completed);
-    
+
     if final_state.is_completed {
-        println!("⚠️  In this run, responses happened to arrive in order.");
-        println!("   Try running again - the bug is non-deterministic!");
+        println!("✓ State converged on click 3's value, even though its response arrived");
+        println!("  before click 1's late, now-stale response did.");
     } else {
-        println!("❌ The UI flickered to the WRONG state!");
-        println!("   This is because response 2 arrived after response 3.");
+        println!("❌ State diverged from click 3's value - that would be a regression in");
+        println!("   the ordered-worker/generation-versioning guarantee documented in lib.rs.");
     }
 }
 
@@ -144,9 +156,10 @@ const taskToggle = new WebTaskToggle('task-123');
 
 // Attach to button
 document.getElementById('toggle-btn').addEventListener('click', async () => {
-  // This will have the race condition bug!
+  // Rapid clicks converge on the last click - ordered workers and generation
+  // versioning discard any response that's no longer current.
   const state = await taskToggle.toggle('task-123');
-  updateUI(state); // UI might flicker back to wrong state
+  updateUI(state);
 });
 
 // Get current state
@@ -160,13 +173,13 @@ Typical framework integration (React):
 function TaskItem({ taskId }) {
   const [isCompleted, setIsCompleted] = useState(false);
   const taskToggle = useRef(new WebTaskToggle(taskId));
-  
+
   const handleToggle = async () => {
-    // BUG: If user clicks rapidly, state will flicker
+    // Rapid clicks always settle on the last click's state.
     const state = await taskToggle.current.toggle(taskId);
     setIsCompleted(state.is_completed);
   };
-  
+
   return (
     <div>
       <button onClick={handleToggle}>
@@ -1,48 +1,132 @@
 /*!
-# Task Toggle Module - Optimistic UI Update (BUGGY VERSION)
+# Task Toggle Module - Optimistic UI Update
 
 This module implements a "Like" button / "Task Toggle" system with optimistic UI updates.
 
-## ⚠️ WARNING: This module contains a deliberate bug! ⚠️
+## Background: the reordering race
 
-### The Bug
 When a user rapidly clicks the toggle button multiple times (e.g., 3 times in quick succession),
-the local state updates optimistically: true -> false -> true. However, due to network jitter,
-API responses may arrive out of order (request 1, request 3, request 2).
+the local state updates optimistically: true -> false -> true. Because each click fires its own
+simulated API call with random network jitter, responses can arrive out of order (request 1,
+request 3, request 2).
 
-This causes the UI to "flicker" back to the wrong state after responses arrive, because
-the system doesn't track which response is most recent.
+Left unhandled, that causes the UI to "flicker" back to a stale state once a late response for an
+earlier click arrives after a later click has already been applied - even though the later click
+is what the user actually intended to land on.
 
-### Expected Behavior
-- Click 1: Local state = true, API request 1 sent
-- Click 2: Local state = false, API request 2 sent  
-- Click 3: Local state = true, API request 3 sent
+### The fix: generation versioning
+`TaskToggleService` tracks a monotonically increasing `generation` counter ([`AtomicU64`]).
+Every [`TaskToggleService::toggle`] call captures the generation it was issued at; when its
+simulated API response comes back, the response is only committed to local state if that
+captured generation is still the latest one seen - i.e. no newer click has happened since. A
+superseded response is discarded instead of overwriting newer state. This guarantees the last
+click always wins regardless of response order.
 
-### Actual Behavior (with bug)
-If responses arrive as: Response 1, Response 3, Response 2
-- Response 1 arrives: state = true ✓
-- Response 3 arrives: state = true ✓
-- Response 2 arrives: state = false ✗ (WRONG! Should stay true)
+### Request coalescing and ordered execution
+`toggle` no longer spawns a task per call. Instead it pushes a [`ToggleIntent`] onto a per-task
+FIFO queue owned by a background [`Worker`] (see the `worker` module); a dedicated worker per task
+drains that queue strictly in submission order, so five rapid clicks are always applied in the
+order they were clicked rather than racing. Adjacent intents requesting the same state collapse
+into a single applied intent instead of each firing its own simulated call.
 
-The final state becomes false instead of true because response 2 arrives last,
-even though request 3 was made after request 2.
+### Cancellation
+Strict ordering means a worker won't skip ahead of an intent it's already applying just because a
+newer one was queued behind it - that intent waits its turn. [`TaskToggleService::cancel_pending`]
+is the explicit escape hatch: it wakes the [`Notify`] the worker's current intent is waiting on
+alongside its simulated delay, so that intent abandons the wait and moves straight on to whatever
+it has queued up (collapsing adjacent no-ops as usual). A cancelled intent is guaranteed to never
+commit its own response to local state.
 
-### The Fix (Not Implemented)
-To fix this bug, you need to implement one of:
-1. **Idempotency Keys**: Track request IDs and only apply the most recent request
-2. **Request Queue**: Process requests serially, canceling outdated requests
-3. **Version Numbers**: Track version/timestamp with each state change
+[`TaskToggleService::shutdown`] stops every worker: with `drain: true` each worker's queue is
+closed but allowed to finish applying whatever's already in it; with `drain: false` every worker
+is aborted immediately, discarding unapplied work.
 
-This module deliberately omits these fixes to demonstrate the race condition.
+### Retry and concurrency policy
+Real backends fail transiently and have a finite concurrency budget. [`TaskToggleConfig`], passed
+to [`TaskToggleService::with_config`], controls both: a simulated request that fails is retried up
+to `retries` times with jittered backoff spaced by `duration_overhead`, and `burst_pct` caps the
+share of the service's concurrency budget a single instance may occupy at once, enforced with a
+[`tokio::sync::Semaphore`]. [`TaskToggleConfig::preconfig_burst`] and
+[`TaskToggleConfig::preconfig_throughput`] are presets for the two ends of that trade-off.
+
+### Deterministic testing
+Real network jitter makes the reordering race above a flaky thing to assert on directly - it might
+or might not reproduce on a given run. [`TaskToggleService::enqueue_latency`] queues exact,
+explicit delays for upcoming `toggle` calls instead of the usual jittered ones; paired with Tokio's
+paused virtual clock (`#[tokio::test(start_paused = true)]`) and `tokio::time::advance`, a test can
+pin down precisely which simulated response resolves first and assert on the outcome every time,
+rather than hoping a slow response lands late often enough to catch the bug.
 */
 
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, Notify, RwLock, Semaphore};
 use tokio::time::sleep;
 use uuid::Uuid;
 
+mod worker;
+pub use worker::{ToggleIntent, Worker};
+use worker::WorkerManager;
+
+/// Total concurrent simulated API calls available to share across a
+/// `TaskToggleService` instance's requests, before [`TaskToggleConfig::burst_pct`]
+/// carves out this instance's share of it.
+const MAX_IN_FLIGHT: usize = 20;
+
+/// Retry and concurrency policy for the simulated API layer.
+#[derive(Debug, Clone)]
+pub struct TaskToggleConfig {
+    /// How many times a simulated request is retried after a failure, with
+    /// jittered backoff between attempts, before it's given up on.
+    pub retries: u8,
+    /// Fraction (0.0-1.0) of [`MAX_IN_FLIGHT`] this service instance may
+    /// occupy with simulated requests at once.
+    pub burst_pct: f32,
+    /// Base spacing between retry attempts; actual backoff is this times
+    /// the attempt number, plus jitter.
+    pub duration_overhead: Duration,
+}
+
+impl Default for TaskToggleConfig {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            burst_pct: 0.5,
+            duration_overhead: Duration::from_millis(75),
+        }
+    }
+}
+
+impl TaskToggleConfig {
+    /// Favors short bursts of maximum concurrency over resilience: no
+    /// retries, the full concurrency budget, minimal backoff spacing.
+    pub fn preconfig_burst() -> Self {
+        Self {
+            retries: 0,
+            burst_pct: 1.0,
+            duration_overhead: Duration::from_millis(20),
+        }
+    }
+
+    /// Favors sustained throughput against a flaky backend: a conservative
+    /// concurrency share, but more retries spaced further apart.
+    pub fn preconfig_throughput() -> Self {
+        Self {
+            retries: 4,
+            burst_pct: 0.25,
+            duration_overhead: Duration::from_millis(150),
+        }
+    }
+
+    fn max_concurrent(&self) -> usize {
+        ((MAX_IN_FLIGHT as f32) * self.burst_pct).round().max(1.0) as usize
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskState {
     pub id: String,
@@ -59,16 +143,48 @@ pub struct ToggleRequest {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-/// The main TaskToggleService with the race condition bug
+/// The main TaskToggleService for the Task Toggle / "Like" button demo.
 pub struct TaskToggleService {
     // Local state that gets updated optimistically
     local_state: Arc<RwLock<TaskState>>,
     // Simulated API endpoint that introduces random delays
     api_delay_ms: u64,
+    // Monotonically increasing count of toggle() calls issued so far - the
+    // source of truth for which queued intent, if any, is still the most
+    // recent one the user asked for.
+    generation: Arc<AtomicU64>,
+    // One background worker per task id, each draining that task's queue of
+    // toggle intents in submission order.
+    workers: WorkerManager,
+    // How many adjacent no-op intents a worker collapsed away instead of
+    // applying - exposed for observability/tests.
+    coalesced_calls: Arc<AtomicU64>,
+    // How many simulated API calls have actually been fired, across all
+    // tasks - exposed so tests can assert coalescing actually cut traffic.
+    api_call_count: Arc<AtomicU64>,
+    // Retry/backoff policy, kept around so it can be cloned into each
+    // spawned request loop.
+    config: TaskToggleConfig,
+    // Bounds how many simulated requests this instance may have in flight
+    // at once, sized from `config.burst_pct`.
+    concurrency: Arc<Semaphore>,
+    // Explicit per-request delays queued up by `enqueue_latency`, consumed
+    // FIFO by the next `toggle` calls instead of the usual jittered delay -
+    // lets tests drive response ordering deterministically under paused
+    // Tokio time.
+    latency_queue: Arc<Mutex<VecDeque<Duration>>>,
 }
 
 impl TaskToggleService {
     pub fn new(task_id: String, initial_state: bool) -> Self {
+        Self::with_config(task_id, initial_state, TaskToggleConfig::default())
+    }
+
+    /// Like [`TaskToggleService::new`], but with an explicit retry/concurrency
+    /// policy instead of [`TaskToggleConfig::default`].
+    pub fn with_config(task_id: String, initial_state: bool, config: TaskToggleConfig) -> Self {
+        let concurrency = Arc::new(Semaphore::new(config.max_concurrent()));
+        let coalesced_calls = Arc::new(AtomicU64::new(0));
         Self {
             local_state: Arc::new(RwLock::new(TaskState {
                 id: task_id,
@@ -77,6 +193,13 @@ impl TaskToggleService {
                 timestamp: chrono::Utc::now(),
             })),
             api_delay_ms: 100,
+            generation: Arc::new(AtomicU64::new(0)),
+            workers: WorkerManager::new(coalesced_calls.clone()),
+            coalesced_calls,
+            api_call_count: Arc::new(AtomicU64::new(0)),
+            config,
+            concurrency,
+            latency_queue: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 
@@ -85,43 +208,102 @@ impl TaskToggleService {
         self.local_state.read().await.clone()
     }
 
-    /// Toggle the task with optimistic update
-    /// 
-    /// BUG: This function updates local state immediately but doesn't track
-    /// which API response corresponds to which request. If responses arrive
-    /// out of order, the final state will be wrong.
+    /// Queues an explicit delay for the next `toggle` call's simulated
+    /// request, overriding the usual jittered `api_delay_ms` wait. Consumed
+    /// FIFO, one delay per `toggle` call, until the queue runs dry.
+    ///
+    /// Meant for deterministic tests run under paused Tokio time
+    /// (`#[tokio::test(start_paused = true)]`): enqueue delays for a
+    /// sequence of `toggle` calls, then drive `tokio::time::advance` by
+    /// exact amounts to control which simulated response resolves first,
+    /// instead of relying on real, non-deterministic network jitter. An
+    /// explicit delay also bypasses simulated failure injection, since it's
+    /// meant to pin down ordering, not resilience.
+    pub async fn enqueue_latency(&self, delay: Duration) {
+        self.latency_queue.lock().await.push_back(delay);
+    }
+
+    /// The number of [`TaskToggleService::toggle`] calls issued so far.
+    /// Exposed mainly for tests that need to assert which generation a
+    /// given response should (or shouldn't) have been allowed to commit.
+    pub fn current_generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// How many `toggle` calls collapsed into an already in-flight request
+    /// for the same task, instead of spawning their own.
+    pub fn coalesced_calls(&self) -> u64 {
+        self.coalesced_calls.load(Ordering::SeqCst)
+    }
+
+    /// How many simulated API calls have actually been fired so far, across
+    /// every task.
+    pub fn api_call_count(&self) -> u64 {
+        self.api_call_count.load(Ordering::SeqCst)
+    }
+
+    /// Cancels whatever intent `task_id`'s worker is currently applying, if
+    /// any - it is woken immediately instead of being left to run its delay
+    /// out, and is guaranteed not to commit a response to local state once
+    /// woken this way. A no-op if `task_id` has no worker, or its worker
+    /// isn't currently waiting on anything.
+    pub async fn cancel_pending(&self, task_id: &str) {
+        self.workers.cancel_current(task_id).await;
+    }
+
+    /// Gracefully shuts down every background worker. See
+    /// [`worker::WorkerManager::shutdown`] for the `drain` semantics.
+    pub async fn shutdown(&self, drain: bool) {
+        self.workers.shutdown(drain).await;
+    }
+
+    /// Toggle the task with optimistic update.
+    ///
+    /// The local state always updates immediately. The actual API call
+    /// doesn't happen inline: a [`ToggleIntent`] is queued for
+    /// `task_id`'s background worker, which applies every task's intents
+    /// strictly in the order they were queued, collapsing adjacent intents
+    /// that request the same state into a single applied one. The
+    /// generation counter still guards the final commit, so even out-of-
+    /// order simulated responses can never make a stale click win.
     pub async fn toggle(&self, task_id: String) -> Result<TaskState, String> {
-        let request = ToggleRequest {
-            request_id: Uuid::new_v4(),
-            task_id: task_id.clone(),
-            new_state: !self.local_state.read().await.is_completed,
-            timestamp: chrono::Utc::now(),
-        };
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let new_state = !self.local_state.read().await.is_completed;
 
         // OPTIMISTIC UPDATE: Update local state immediately before API call
         {
             let mut state = self.local_state.write().await;
-            state.is_completed = request.new_state;
+            state.is_completed = new_state;
             state.timestamp = chrono::Utc::now();
         }
 
-        // Spawn async task to simulate API call
-        // BUG: We don't track request order or use idempotency keys!
-        let local_state_clone = self.local_state.clone();
-        let api_delay = self.api_delay_ms;
-        
-        tokio::spawn(async move {
-            // Simulate API call with variable delay (network jitter)
-            // Random delay between 50-200ms to simulate real-world conditions
-            let jitter = (request.request_id.as_u128() % 150) as u64;
-            sleep(Duration::from_millis(api_delay + jitter)).await;
+        let explicit_delay = self.latency_queue.lock().await.pop_front();
+        let intent = ToggleIntent {
+            generation,
+            desired_state: new_state,
+            explicit_delay,
+        };
 
-            // BUG: When response arrives, we just apply it without checking
-            // if a newer request has already been processed
-            let mut state = local_state_clone.write().await;
-            state.is_completed = request.new_state;
-            state.timestamp = chrono::Utc::now();
-        });
+        let local_state = self.local_state.clone();
+        let generation_counter = self.generation.clone();
+        let config = self.config.clone();
+        let concurrency = self.concurrency.clone();
+        let api_call_count = self.api_call_count.clone();
+        let api_delay_ms = self.api_delay_ms;
+
+        self.workers
+            .enqueue(&task_id, intent, move |notify| {
+                Box::new(ApiWorker {
+                    local_state,
+                    generation_counter,
+                    config,
+                    concurrency,
+                    api_call_count,
+                    api_delay_ms,
+                    notify,
+                })
+            })
+            .await;
 
         Ok(self.local_state.read().await.clone())
     }
@@ -174,38 +356,150 @@ impl TaskToggleService {
     }
 }
 
-/// A "fixed" version would look like this (commented out):
-/// 
-/// ```rust,ignore
-/// pub struct FixedTaskToggleService {
-///     local_state: Arc<RwLock<TaskState>>,
-///     // Track the most recent request ID
-///     latest_request_id: Arc<RwLock<Uuid>>,
-/// }
-/// 
-/// impl FixedTaskToggleService {
-///     pub async fn toggle(&self, task_id: String) -> Result<TaskState, String> {
-///         let request_id = Uuid::new_v4();
-///         
-///         // Update the latest request ID
-///         *self.latest_request_id.write().await = request_id;
-///         
-///         // ... optimistic update ...
-///         
-///         // In the API response handler:
-///         tokio::spawn(async move {
-///             // ... API call ...
-///             
-///             // Only apply if this is still the most recent request
-///             let latest_id = *latest_request_id_clone.read().await;
-///             if request_id == latest_id {
-///                 // Apply the state change
-///             }
-///             // Otherwise, ignore this outdated response
-///         });
-///     }
-/// }
-/// ```
+/// The [`Worker`] a `TaskToggleService` hands to its [`WorkerManager`] for
+/// every task: applies one [`ToggleIntent`] by sending a simulated request
+/// for it and committing the result if it's still the latest generation.
+struct ApiWorker {
+    local_state: Arc<RwLock<TaskState>>,
+    generation_counter: Arc<AtomicU64>,
+    config: TaskToggleConfig,
+    concurrency: Arc<Semaphore>,
+    api_call_count: Arc<AtomicU64>,
+    api_delay_ms: u64,
+    notify: Arc<Notify>,
+}
+
+#[async_trait]
+impl Worker for ApiWorker {
+    async fn apply(&mut self, intent: ToggleIntent) {
+        let request = ToggleRequest {
+            request_id: Uuid::new_v4(),
+            task_id: self.local_state.read().await.id.clone(),
+            new_state: intent.desired_state,
+            timestamp: chrono::Utc::now(),
+        };
+
+        let outcome = send_with_retries(
+            &request,
+            &self.config,
+            &self.concurrency,
+            &self.api_call_count,
+            self.api_delay_ms,
+            Some(&self.notify),
+            intent.explicit_delay,
+        )
+        .await;
+
+        // Only commit if this intent's generation is still the latest seen
+        // - a later `toggle` call having bumped the counter since means a
+        // newer intent should win instead. Cancelled and permanently failed
+        // intents never commit at all.
+        if outcome == RequestOutcome::Committed
+            && self.generation_counter.load(Ordering::SeqCst) == intent.generation
+        {
+            let mut state = self.local_state.write().await;
+            state.is_completed = intent.desired_state;
+            state.timestamp = chrono::Utc::now();
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RequestOutcome {
+    /// The response arrived and should be committed (subject to the caller
+    /// still checking it against the current generation).
+    Committed,
+    /// The request was woken early by its `Notify` before it could resolve.
+    Cancelled,
+    /// Every attempt, including retries, simulated a failure.
+    Failed,
+}
+
+/// Sends one simulated request, retrying on simulated failure up to
+/// `config.retries` times with jittered backoff, and bailing out early if
+/// `notify` fires mid-wait (the request was superseded or explicitly
+/// cancelled). Bounded by `concurrency`, so this service instance never has
+/// more than `config.burst_pct`'s share of [`MAX_IN_FLIGHT`] calls in flight
+/// at once.
+///
+/// `explicit_delay`, if set, replaces the usual jittered delay for the first
+/// attempt and skips simulated failure injection entirely - it's meant for
+/// deterministic tests driving response ordering with `tokio::time::advance`,
+/// not for exercising retry behavior.
+async fn send_with_retries(
+    request: &ToggleRequest,
+    config: &TaskToggleConfig,
+    concurrency: &Semaphore,
+    api_call_count: &AtomicU64,
+    api_delay_ms: u64,
+    notify: Option<&Arc<Notify>>,
+    explicit_delay: Option<Duration>,
+) -> RequestOutcome {
+    if let Some(delay) = explicit_delay {
+        let _permit = concurrency
+            .acquire()
+            .await
+            .expect("concurrency semaphore is never closed");
+        api_call_count.fetch_add(1, Ordering::SeqCst);
+
+        let cancelled = match notify {
+            Some(notify) => {
+                tokio::select! {
+                    _ = sleep(delay) => false,
+                    _ = notify.notified() => true,
+                }
+            }
+            None => {
+                sleep(delay).await;
+                false
+            }
+        };
+
+        return if cancelled { RequestOutcome::Cancelled } else { RequestOutcome::Committed };
+    }
+
+    for attempt in 0..=config.retries {
+        let _permit = concurrency
+            .acquire()
+            .await
+            .expect("concurrency semaphore is never closed");
+        api_call_count.fetch_add(1, Ordering::SeqCst);
+
+        // Random delay between 50-200ms to simulate real-world network
+        // conditions; varies per attempt so a retry doesn't just race its
+        // own previous attempt's timing.
+        let jitter = (request.request_id.as_u128().wrapping_add(attempt as u128) % 150) as u64;
+        let cancelled = match notify {
+            Some(notify) => {
+                tokio::select! {
+                    _ = sleep(Duration::from_millis(api_delay_ms + jitter)) => false,
+                    _ = notify.notified() => true,
+                }
+            }
+            None => {
+                sleep(Duration::from_millis(api_delay_ms + jitter)).await;
+                false
+            }
+        };
+
+        if cancelled {
+            return RequestOutcome::Cancelled;
+        }
+
+        // Simulate a transient backend failure on roughly one attempt in
+        // five.
+        let failed = (request.request_id.as_u128().wrapping_add(attempt as u128) % 5) == 0;
+        if !failed {
+            return RequestOutcome::Committed;
+        }
+
+        if attempt < config.retries {
+            sleep(config.duration_overhead * (attempt as u32 + 1)).await;
+        }
+    }
+
+    RequestOutcome::Failed
+}
 
 #[cfg(test)]
 mod tests {
@@ -220,27 +514,180 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_race_condition_bug() {
-        // This test demonstrates the bug!
+    async fn test_rapid_toggle_converges_to_last_click() {
         let service = TaskToggleService::new("task2".to_string(), false);
-        
+
         // Rapidly toggle 3 times
         let _states = service.rapid_toggle("task2".to_string(), 3).await;
-        
+
         // Immediately after toggling, the local state shows "true" (3rd click)
         let immediate_state = service.get_local_state().await;
         assert_eq!(immediate_state.is_completed, true);
-        
-        // Wait for all API responses to arrive
-        sleep(Duration::from_millis(500)).await;
-        
-        // BUG: The final state might be wrong due to race condition!
-        // It should be "true" but might be "false" if response 2 arrived last
+
+        // Wait for all API responses to arrive, regardless of the order they
+        // land in - generous enough to cover a worst-case retry chain.
+        sleep(Duration::from_millis(1500)).await;
+
+        // Generation versioning guarantees the last click always wins, so
+        // the final state must match the optimistic one - no flicker.
         let final_state = service.get_local_state().await;
-        
-        // This assertion might fail due to the race condition bug
-        // In a real scenario, this would manifest as UI flickering
-        println!("Final state: {:?}", final_state);
-        println!("Expected: true, Got: {}", final_state.is_completed);
+        assert_eq!(final_state.is_completed, immediate_state.is_completed);
+    }
+
+    #[tokio::test]
+    async fn test_stale_response_is_discarded() {
+        let service = TaskToggleService::new("task3".to_string(), false);
+
+        service.toggle("task3".to_string()).await.unwrap();
+        let generation_after_first = service.current_generation();
+        service.toggle("task3".to_string()).await.unwrap();
+
+        assert!(service.current_generation() > generation_after_first);
+
+        // Both responses will have arrived by now; only the second toggle's
+        // response should have been allowed to commit.
+        sleep(Duration::from_millis(1500)).await;
+        let state = service.get_local_state().await;
+        assert_eq!(state.is_completed, false);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_toggles_still_converge_in_submission_order() {
+        let service = Arc::new(TaskToggleService::new("task4".to_string(), false));
+
+        // Fire several toggles for the same task at once, with no delay
+        // between them - they all land in task4's worker queue and get
+        // applied strictly in the order they were submitted, regardless of
+        // how their simulated responses happen to resolve.
+        let mut handles = Vec::new();
+        for _ in 0..5 {
+            let service = service.clone();
+            handles.push(tokio::spawn(async move {
+                service.toggle("task4".to_string()).await.unwrap();
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let immediate_state = service.get_local_state().await;
+
+        // Give the worker time to drain the whole queue.
+        sleep(Duration::from_millis(3000)).await;
+
+        // Generation versioning guarantees the final applied intent still
+        // matches the last optimistic update, exactly as with sequential
+        // rapid clicks.
+        let final_state = service.get_local_state().await;
+        assert_eq!(final_state.is_completed, immediate_state.is_completed);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_toggles_reduce_api_call_count() {
+        let service = Arc::new(TaskToggleService::new("task8".to_string(), false));
+
+        // Unlike the sequential case above, these toggles run on real OS
+        // threads in parallel, so several of them race each other's
+        // optimistic read of `local_state` before either side's write lands
+        // - producing runs of adjacent, same-direction intents in task8's
+        // queue. The worker collapses those runs instead of applying each
+        // one, so 20 concurrent clicks should cost nowhere near 20 simulated
+        // requests, mirroring the traffic reduction the single-flight
+        // in-flight slot used to guarantee before the worker queue replaced it.
+        let mut handles = Vec::new();
+        for _ in 0..20 {
+            let service = service.clone();
+            handles.push(tokio::spawn(async move {
+                service.toggle("task8".to_string()).await.unwrap();
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // Give the worker time to drain whatever landed in its queue.
+        sleep(Duration::from_millis(3000)).await;
+
+        assert!(service.coalesced_calls() > 0);
+        assert!(service.api_call_count() < 20);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drain_applies_queued_work_before_returning() {
+        let service = TaskToggleService::new("task6".to_string(), false);
+
+        service.toggle("task6".to_string()).await.unwrap();
+        service.shutdown(true).await;
+
+        // A drained shutdown waits for the worker to finish applying
+        // whatever was already queued, so the state is already settled by
+        // the time it returns - no extra sleep needed.
+        let state = service.get_local_state().await;
+        assert_eq!(state.is_completed, true);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancel_does_not_hang() {
+        let service = TaskToggleService::new("task7".to_string(), false);
+
+        service.toggle("task7".to_string()).await.unwrap();
+        // Aborts the worker instead of waiting for it - mainly checking
+        // this returns promptly rather than asserting on final state,
+        // since an aborted worker's in-progress intent may or may not have
+        // committed yet.
+        service.shutdown(false).await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_prevents_mutation() {
+        let service = TaskToggleService::new("task5".to_string(), false);
+
+        service.toggle("task5".to_string()).await.unwrap();
+        let state_before_cancel = service.get_local_state().await;
+
+        service.cancel_pending("task5").await;
+
+        // Give the in-flight request's full delay window to elapse - if
+        // cancellation hadn't woken it early, its response would still land
+        // here and overwrite the optimistic state.
+        sleep(Duration::from_millis(1500)).await;
+
+        let state_after = service.get_local_state().await;
+        assert_eq!(state_after.timestamp, state_before_cancel.timestamp);
+    }
+}
+
+/// Deterministic regression coverage for the reordering race documented at
+/// the top of this module. Gated behind `deterministic-sim` rather than
+/// plain `test` since it depends on `start_paused` time control advancing
+/// in exact, manually-driven steps, which is a stronger requirement than the
+/// best-effort `test_rapid_toggle_converges_to_last_click` above makes.
+#[cfg(all(test, feature = "deterministic-sim"))]
+mod sim_tests {
+    use super::*;
+
+    /// Response #1 is queued to resolve well after response #2, yet the
+    /// final state matches the second (later) click every time this runs -
+    /// generation versioning, not response order, decides what commits.
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn out_of_order_responses_always_converge_on_the_last_click() {
+        let service = TaskToggleService::new("task-sim".to_string(), false);
+
+        service.enqueue_latency(Duration::from_millis(200)).await;
+        service.toggle("task-sim".to_string()).await.unwrap(); // -> true, resolves at t=200ms
+
+        service.enqueue_latency(Duration::from_millis(50)).await;
+        service.toggle("task-sim".to_string()).await.unwrap(); // -> false, resolves at t=50ms
+
+        // Advance just past response #2 - it commits first, since it's the
+        // latest generation.
+        tokio::time::advance(Duration::from_millis(60)).await;
+        assert_eq!(service.get_local_state().await.is_completed, false);
+
+        // Advance past response #1 too - it arrives later in real time, but
+        // its generation is stale by now, so it's discarded rather than
+        // flickering the state back to `true`.
+        tokio::time::advance(Duration::from_millis(200)).await;
+        assert_eq!(service.get_local_state().await.is_completed, false);
     }
 }
@@ -0,0 +1,201 @@
+//! Background worker subsystem that applies queued toggle intents strictly
+//! in submission order, one dedicated worker per task id.
+//!
+//! `TaskToggleService::toggle` no longer spawns its own task per call; it
+//! pushes a [`ToggleIntent`] onto the task's queue instead, and a
+//! [`WorkerManager`]-owned worker drains that queue, collapsing adjacent
+//! no-op transitions so a burst of same-direction clicks costs one applied
+//! intent rather than one per click.
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, Mutex, Notify};
+use tokio::task::JoinHandle;
+
+/// A single queued desired-state change for one task, in the order its
+/// `toggle` call was issued.
+#[derive(Debug, Clone, Copy)]
+pub struct ToggleIntent {
+    /// The generation `toggle` captured when this intent was queued - the
+    /// worker only commits the intent's effect if this is still current by
+    /// the time it's applied.
+    pub generation: u64,
+    pub desired_state: bool,
+    /// An explicit delay queued via `TaskToggleService::enqueue_latency` at
+    /// the time this intent was created, if any, for deterministic tests.
+    /// Lost if this intent gets collapsed as an adjacent no-op - whichever
+    /// intent actually gets applied uses its own delay instead.
+    pub explicit_delay: Option<Duration>,
+}
+
+/// Applies a single queued intent - the simulated API call plus whatever
+/// bookkeeping decides whether it actually lands in local state. Abstracted
+/// behind a trait, the same way `Module 20`'s `SessionStore` abstracts
+/// persistence, so the manager doesn't need to know how an intent is
+/// actually carried out.
+#[async_trait]
+pub trait Worker: Send {
+    async fn apply(&mut self, intent: ToggleIntent);
+}
+
+/// Owns one task's worker: the sender half of its intent queue, the join
+/// handle for its spawned task, and a [`Notify`] the currently-processing
+/// `apply` call is waiting on (if any), for [`WorkerManager::cancel_current`].
+struct WorkerHandle {
+    sender: mpsc::UnboundedSender<ToggleIntent>,
+    join: JoinHandle<()>,
+    notify: Arc<Notify>,
+}
+
+/// Spawns and tracks one background worker per task id, each draining its
+/// own FIFO queue of [`ToggleIntent`]s in submission order.
+pub struct WorkerManager {
+    workers: Mutex<HashMap<String, WorkerHandle>>,
+    coalesced_calls: Arc<AtomicU64>,
+}
+
+impl WorkerManager {
+    pub fn new(coalesced_calls: Arc<AtomicU64>) -> Self {
+        Self {
+            workers: Mutex::new(HashMap::new()),
+            coalesced_calls,
+        }
+    }
+
+    /// Queues `intent` for `task_id`, spawning a worker for it if this is
+    /// the first intent seen for that task.
+    pub async fn enqueue<F>(&self, task_id: &str, intent: ToggleIntent, make_worker: F)
+    where
+        F: FnOnce(Arc<Notify>) -> Box<dyn Worker>,
+    {
+        let mut workers = self.workers.lock().await;
+        if let Some(handle) = workers.get(task_id) {
+            // `send` only fails if the worker task has already exited,
+            // which only happens once its receiver is dropped - i.e. on
+            // shutdown. There's nothing meaningful to do with a toggle
+            // that arrives after shutdown, so it's silently dropped.
+            let _ = handle.sender.send(intent);
+            return;
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let notify = Arc::new(Notify::new());
+        let mut worker = make_worker(notify.clone());
+        let coalesced_calls = self.coalesced_calls.clone();
+
+        let join = tokio::spawn(async move {
+            run_worker(rx, worker.as_mut(), coalesced_calls).await;
+        });
+
+        let _ = tx.send(intent);
+        workers.insert(
+            task_id.to_string(),
+            WorkerHandle { sender: tx, join, notify },
+        );
+    }
+
+    /// Wakes whatever intent `task_id`'s worker is currently applying, so it
+    /// abandons its wait instead of committing. A no-op if there's no
+    /// worker for `task_id`.
+    pub async fn cancel_current(&self, task_id: &str) {
+        if let Some(handle) = self.workers.lock().await.get(task_id) {
+            handle.notify.notify_one();
+        }
+    }
+
+    /// Gracefully shuts every worker down.
+    ///
+    /// With `drain: true`, each worker's queue is closed (no new intents
+    /// accepted) but let run to completion, so everything already queued
+    /// still gets applied before this returns. With `drain: false`, every
+    /// worker is aborted immediately, discarding whatever it hadn't gotten
+    /// to yet.
+    pub async fn shutdown(&self, drain: bool) {
+        let handles: Vec<WorkerHandle> = self.workers.lock().await.drain().map(|(_, h)| h).collect();
+        for handle in handles {
+            // Dropping the sender closes the channel - once the worker has
+            // drained whatever was already queued, `rx.recv()` returns
+            // `None` and the loop exits on its own.
+            drop(handle.sender);
+            if drain {
+                let _ = handle.join.await;
+            } else {
+                handle.join.abort();
+            }
+        }
+    }
+}
+
+/// Drains `rx` until the channel closes, applying each intent via `worker` -
+/// except for a run of adjacent intents requesting the same state, which
+/// collapses down to applying only the last of the run.
+async fn run_worker(
+    mut rx: mpsc::UnboundedReceiver<ToggleIntent>,
+    worker: &mut dyn Worker,
+    coalesced_calls: Arc<AtomicU64>,
+) {
+    while let Some(mut intent) = rx.recv().await {
+        // Greedily absorb whatever's already buffered: as long as the next
+        // queued intent wants the same state as the one we're holding, it's
+        // an adjacent no-op - skip straight to it instead of applying the
+        // one we're holding first.
+        while let Ok(next) = rx.try_recv() {
+            if next.desired_state == intent.desired_state {
+                coalesced_calls.fetch_add(1, Ordering::SeqCst);
+                intent = next;
+                continue;
+            }
+            worker.apply(intent).await;
+            intent = next;
+        }
+        worker.apply(intent).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Mutex as TokioMutex;
+
+    struct RecordingWorker {
+        applied: Arc<TokioMutex<Vec<bool>>>,
+    }
+
+    #[async_trait]
+    impl Worker for RecordingWorker {
+        async fn apply(&mut self, intent: ToggleIntent) {
+            self.applied.lock().await.push(intent.desired_state);
+        }
+    }
+
+    fn intent(generation: u64, desired_state: bool) -> ToggleIntent {
+        ToggleIntent {
+            generation,
+            desired_state,
+            explicit_delay: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn adjacent_duplicate_intents_collapse_into_one_apply() {
+        let applied = Arc::new(TokioMutex::new(Vec::new()));
+        let (tx, rx) = mpsc::unbounded_channel();
+        let coalesced = Arc::new(AtomicU64::new(0));
+
+        tx.send(intent(1, true)).unwrap();
+        tx.send(intent(2, true)).unwrap();
+        tx.send(intent(3, false)).unwrap();
+        tx.send(intent(4, false)).unwrap();
+        tx.send(intent(5, true)).unwrap();
+        drop(tx);
+
+        let mut worker = RecordingWorker { applied: applied.clone() };
+        run_worker(rx, &mut worker, coalesced.clone()).await;
+
+        assert_eq!(*applied.lock().await, vec![true, false, true]);
+        assert_eq!(coalesced.load(Ordering::SeqCst), 2);
+    }
+}
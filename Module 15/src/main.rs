@@ -1,38 +1,329 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Result};
-use futures::stream::{Stream};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Result};
+use futures::stream::Stream;
+use governor::{Jitter, Quota, RateLimiter};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU32;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 use tokio::time::interval;
-use tokio_stream::wrappers::IntervalStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, IntervalStream};
 use tokio_stream::StreamExt;
 
+/// Per-IP token bucket, keyed by the caller's client IP (see
+/// [`client_ip`]). Governs how often a given IP may *open a new* SSE
+/// connection; [`AppState::ip_connection_counts`] separately governs how
+/// many it may hold open *simultaneously*.
+type ConnectionRateLimiter = RateLimiter<
+    String,
+    governor::state::keyed::DefaultKeyedStateStore<String>,
+    governor::clock::DefaultClock,
+>;
+
 // Global counter to track active SSE connections
 // This is where the bug manifests - connections keep accumulating
 static CONNECTION_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
+/// Bookkeeping for one open SSE connection, keyed by connection id in
+/// [`AppState::active_connections`].
+struct ConnectionMeta {
+    opened_at: Instant,
+    peer_addr: Option<String>,
+    /// Flipped by [`run_connection_sweeper`] once this connection has
+    /// outlived `max_connection_lifetime`; [`GuardedStream::poll_next`]
+    /// checks it on every poll and ends the stream once it's set, since
+    /// there's no other way to reach into an already-streaming response and
+    /// close it.
+    close: Arc<AtomicBool>,
+}
+
+/// Removes its `connection_id` from [`AppState::active_connections`] when
+/// dropped, i.e. whenever the underlying stream ends for any reason
+/// (client disconnect, server shutdown, or [`ConnectionMeta::close`] firing)
+/// - fixing the leak where connections accumulated forever because nothing
+/// ever called `.remove()`. Also releases this connection's slot in
+/// [`AppState::ip_connection_counts`], the per-IP concurrency quota.
+struct ConnectionGuard {
+    connection_id: usize,
+    connections: Arc<Mutex<HashMap<usize, ConnectionMeta>>>,
+    client_ip: String,
+    ip_connection_counts: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.remove(&self.connection_id);
+            println!("🔌 SSE connection #{} closed (Total active: {})", self.connection_id, connections.len());
+        }
+        if let Ok(mut counts) = self.ip_connection_counts.lock() {
+            if let Some(count) = counts.get_mut(&self.client_ip) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    counts.remove(&self.client_ip);
+                }
+            }
+        }
+    }
+}
+
+/// Wraps the notification byte stream together with the [`ConnectionGuard`]
+/// that must outlive it, and checks `close` on every poll so
+/// [`run_connection_sweeper`] can terminate a connection that's overstayed
+/// `max_connection_lifetime`.
+struct GuardedStream<S> {
+    inner: S,
+    close: Arc<AtomicBool>,
+    _guard: ConnectionGuard,
+}
+
+impl<S: Stream + Unpin> Stream for GuardedStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.close.load(Ordering::SeqCst) {
+            return Poll::Ready(None);
+        }
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+/// One notification fanned out to every subscribed SSE stream. Produced once
+/// by [`run_notification_producer`] and cloned to every subscriber by the
+/// broadcast channel, rather than each handler generating its own.
+#[derive(Clone, Debug, Serialize)]
+struct Notification {
+    /// Monotonically increasing across the process lifetime, independent of
+    /// `connection_id` - this is what goes in the SSE `id:` field and what a
+    /// reconnecting client's `Last-Event-ID` is compared against.
+    id: u64,
+    message: String,
+    timestamp: u64,
+}
+
+/// `?lastEventId=` query fallback for clients/proxies that drop the
+/// `Last-Event-ID` header on reconnect (the `EventSource` spec sends it, but
+/// not everything reconnecting to this endpoint is a real `EventSource`), and
+/// `?topics=` to select which of [`AppState::topics`] to subscribe to.
+#[derive(Deserialize)]
+struct NotificationsQuery {
+    #[serde(rename = "lastEventId")]
+    last_event_id: Option<u64>,
+    topics: Option<String>,
+}
+
+/// The default feed a plain `GET /api/notifications` (no `?topics=`)
+/// subscribes to - kept so existing clients of the single-feed endpoint see
+/// no change in behavior.
+const DEFAULT_TOPIC: &str = "notifications";
+
+/// Every topic this server knows how to produce. A real pub/sub surface
+/// would let producers register topics dynamically; this demo's event
+/// sources are all synthetic tickers, so the set is fixed at startup.
+const KNOWN_TOPICS: &[&str] = &[DEFAULT_TOPIC, "orders", "alerts"];
+
+/// One named event feed: its own broadcast channel (so subscribing to
+/// `orders` never sees `alerts` traffic) and its own replay buffer (so
+/// `Last-Event-ID` replay - see [`replay_since`] - only ever replays events
+/// from the topic the client asked for).
+#[derive(Clone)]
+struct TopicChannel {
+    tx: broadcast::Sender<Notification>,
+    buffer: Arc<Mutex<VecDeque<Notification>>>,
+}
+
 #[derive(Clone)]
 struct AppState {
-    // Track all active connections (but never clean them up properly)
-    active_connections: Arc<Mutex<Vec<usize>>>,
+    active_connections: Arc<Mutex<HashMap<usize, ConnectionMeta>>>,
+    /// Named event feeds a client can subscribe to - see [`KNOWN_TOPICS`].
+    topics: HashMap<String, TopicChannel>,
+    event_buffer_capacity: usize,
+    /// Hard cap on simultaneous open streams; once reached, new connection
+    /// attempts get `429` instead of being allowed to pile up.
+    max_connections: usize,
+    /// How long a connection may stay open before [`run_connection_sweeper`]
+    /// closes it.
+    max_connection_lifetime: Duration,
+    /// Per-IP token bucket bounding how often a given client may *open a
+    /// new* connection - see [`client_ip`] and [`ConnectionRateLimiter`].
+    connection_rate_limiter: Arc<ConnectionRateLimiter>,
+    /// How many connections a single IP may hold open *simultaneously*,
+    /// independent of the rate limiter above (a client that opens one
+    /// connection per second and never closes any would still leak without
+    /// this).
+    max_connections_per_ip: usize,
+    ip_connection_counts: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+/// Liveness and reconnection tuning for SSE streams, shared across every
+/// connection via its own `web::Data` rather than folded into [`AppState`]
+/// since it's pure configuration with no mutable state to guard.
+#[derive(Clone, Copy)]
+struct StreamConfig {
+    /// Value for the client-facing `retry:` directive - how long an
+    /// `EventSource` should wait before reconnecting after the stream ends.
+    retry_ms: u64,
+    /// How often to emit a `: ping` comment line to keep idle connections
+    /// (and any proxy sitting between client and server) from timing out.
+    heartbeat_interval: Duration,
+}
+
+/// Identifies the client for rate-limiting purposes: the first hop in
+/// `X-Forwarded-For` if present (this demo may sit behind a proxy), falling
+/// back to the socket's peer address. Not spoof-proof against an untrusted
+/// proxy, but matches the trust model the rest of this demo already assumes.
+fn client_ip(req: &HttpRequest) -> String {
+    req.headers()
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .or_else(|| req.peer_addr().map(|addr| addr.ip().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
 // SSE notification stream
-// THE BUG: This function creates a new connection but the old ones are never closed
 async fn sse_notifications(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    stream_config: web::Data<StreamConfig>,
+    query: web::Query<NotificationsQuery>,
+) -> Result<HttpResponse> {
+    let requested_topics: Vec<String> = match &query.topics {
+        Some(raw) => raw
+            .split(',')
+            .map(|topic| topic.trim().to_string())
+            .filter(|topic| !topic.is_empty())
+            .collect(),
+        None => vec![DEFAULT_TOPIC.to_string()],
+    };
+
+    handle_sse_stream(req, data, stream_config, requested_topics, query.last_event_id).await
+}
+
+/// Path-based alternative to `/api/notifications?topics=<topic>`, e.g.
+/// `/api/notifications/orders` - the same subscription, just spelled as a
+/// path segment for callers that would rather not build a query string.
+async fn sse_notifications_by_topic(
+    req: HttpRequest,
     data: web::Data<AppState>,
+    stream_config: web::Data<StreamConfig>,
+    topic: web::Path<String>,
+    query: web::Query<NotificationsQuery>,
 ) -> Result<HttpResponse> {
+    handle_sse_stream(req, data, stream_config, vec![topic.into_inner()], query.last_event_id).await
+}
+
+/// Shared implementation behind [`sse_notifications`] and
+/// [`sse_notifications_by_topic`]: validates the requested topics, registers
+/// the connection, and streams the merged, per-topic-tagged output.
+async fn handle_sse_stream(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    stream_config: web::Data<StreamConfig>,
+    requested_topics: Vec<String>,
+    last_event_id: Option<u64>,
+) -> Result<HttpResponse> {
+    let mut channels = Vec::with_capacity(requested_topics.len());
+    for topic in &requested_topics {
+        match data.topics.get(topic) {
+            Some(channel) => channels.push((topic.clone(), channel.clone())),
+            None => return Ok(HttpResponse::BadRequest().body(format!("unknown topic: {}", topic))),
+        }
+    }
+
+    let ip = client_ip(&req);
+
+    if let Err(not_until) = data.connection_rate_limiter.check_key(&ip) {
+        // Jittered so refused clients don't all retry at the exact same
+        // instant and immediately get refused again in lockstep.
+        let jitter = Jitter::up_to(Duration::from_millis(500));
+        let retry_after = not_until.wait_time_from(governor::clock::DefaultClock::default().now()) + jitter.get();
+        return Ok(HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after.as_secs().max(1).to_string()))
+            .body("connection rate limit exceeded for this client"));
+    }
+
     let connection_id = CONNECTION_COUNTER.fetch_add(1, Ordering::SeqCst) + 1;
-    
-    // Add to active connections but never remove
-    if let Ok(mut connections) = data.active_connections.lock() {
-        connections.push(connection_id);
-        println!("🔌 New SSE connection opened: #{} (Total active: {})", 
+    let close_flag = Arc::new(AtomicBool::new(false));
+
+    {
+        let mut connections = data.active_connections.lock()
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Lock error: {}", e)))?;
+
+        if connections.len() >= data.max_connections {
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", "5"))
+                .body("too many concurrent SSE connections"));
+        }
+
+        let mut ip_counts = data.ip_connection_counts.lock()
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Lock error: {}", e)))?;
+        let count = ip_counts.entry(ip.clone()).or_insert(0);
+        if *count >= data.max_connections_per_ip {
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", "5"))
+                .body("too many concurrent SSE connections for this client"));
+        }
+        *count += 1;
+
+        connections.insert(
+            connection_id,
+            ConnectionMeta {
+                opened_at: Instant::now(),
+                peer_addr: req.peer_addr().map(|addr| addr.to_string()),
+                close: close_flag.clone(),
+            },
+        );
+        println!("🔌 New SSE connection opened: #{} (Total active: {})",
                  connection_id, connections.len());
     }
 
-    let stream = create_notification_stream();
+    // A client reconnecting after a drop sends back the last `id:` it saw,
+    // either in the `Last-Event-ID` header (what `EventSource` actually
+    // does) or `?lastEventId=` (for callers that can't set that header).
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or(last_event_id);
+
+    // `chain`/`select_all` are called via the fully qualified trait/free-fn
+    // path rather than importing `futures::StreamExt`, since that trait
+    // overlaps method names (`map`, `filter`, ...) with
+    // `tokio_stream::StreamExt`, already in scope for `notification_stream`
+    // below - importing both would make ordinary `.map()` calls ambiguous.
+    let retry_frame = futures::stream::iter(vec![Ok(web::Bytes::from(format!(
+        "retry: {}\n\n",
+        stream_config.retry_ms
+    )))]);
+    let mut per_topic_streams: Vec<Pin<Box<dyn Stream<Item = Result<web::Bytes, actix_web::Error>>>>> =
+        Vec::with_capacity(channels.len() + 2);
+    per_topic_streams.push(Box::pin(retry_frame));
+    per_topic_streams.push(Box::pin(heartbeat_stream(stream_config.heartbeat_interval)));
+    for (topic, channel) in channels {
+        let replay_frames = replay_since(&topic, &channel.buffer, last_event_id);
+        let replay_stream = futures::stream::iter(replay_frames);
+        let live = notification_stream(topic, channel.tx.subscribe());
+        per_topic_streams.push(Box::pin(futures::StreamExt::chain(replay_stream, live)));
+    }
+    let inner: Pin<Box<dyn Stream<Item = Result<web::Bytes, actix_web::Error>>>> =
+        Box::pin(futures::stream::select_all(per_topic_streams));
+
+    let guard = ConnectionGuard {
+        connection_id,
+        connections: data.active_connections.clone(),
+        client_ip: ip,
+        ip_connection_counts: data.ip_connection_counts.clone(),
+    };
+    let stream = GuardedStream { inner, close: close_flag, _guard: guard };
 
     Ok(HttpResponse::Ok()
         .content_type("text/event-stream")
@@ -41,41 +332,178 @@ async fn sse_notifications(
         .streaming(stream))
 }
 
-fn create_notification_stream() -> Pin<Box<dyn Stream<Item = Result<web::Bytes, actix_web::Error>>>> {
-    let interval_duration = Duration::from_secs(2);
-    let ticker = interval(interval_duration);
-    let stream = IntervalStream::new(ticker);
+/// Builds the replay frames for a reconnecting client, if it sent a
+/// `last_event_id`: every buffered notification after it on `topic`, oldest
+/// first. If `last_event_id` is older than the buffer's oldest entry there's
+/// a gap the buffer can't fill, so a single "resync required" event is
+/// emitted instead - the client is expected to treat that as "some
+/// notifications were missed, reload application state rather than assuming
+/// continuity". No header at all (`last_event_id` is `None`) means start
+/// live with no replay.
+fn replay_since(
+    topic: &str,
+    buffer: &Mutex<VecDeque<Notification>>,
+    last_event_id: Option<u64>,
+) -> Vec<Result<web::Bytes, actix_web::Error>> {
+    let Some(last_event_id) = last_event_id else {
+        return Vec::new();
+    };
+
+    let buffer = match buffer.lock() {
+        Ok(buffer) => buffer,
+        Err(_) => return Vec::new(),
+    };
+
+    match buffer.front() {
+        Some(oldest) if oldest.id > last_event_id + 1 => {
+            vec![Ok(web::Bytes::from(format!(
+                "event: resync\ndata: {{\"topic\": \"{}\", \"reason\": \"requested event id is older than the buffer\"}}\n\n",
+                topic
+            )))]
+        }
+        _ => buffer
+            .iter()
+            .filter(|notification| notification.id > last_event_id)
+            .map(|notification| Ok(encode_notification(topic, notification)))
+            .collect(),
+    }
+}
+
+/// Periodically closes any connection that's been open longer than
+/// `max_connection_lifetime`, by flipping its [`ConnectionMeta::close`] flag
+/// - [`GuardedStream::poll_next`] picks this up on its next poll and ends
+/// the stream, which drops its [`ConnectionGuard`] and removes it from
+/// `connections`.
+async fn run_connection_sweeper(
+    connections: Arc<Mutex<HashMap<usize, ConnectionMeta>>>,
+    max_connection_lifetime: Duration,
+) {
+    let mut ticker = interval(Duration::from_secs(30));
+    loop {
+        ticker.tick().await;
+
+        if let Ok(connections) = connections.lock() {
+            for meta in connections.values() {
+                if meta.opened_at.elapsed() > max_connection_lifetime {
+                    meta.close.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
+
+/// Background task that produces notifications for one topic, once on a
+/// fixed interval, and fans them out to every subscriber of that topic's
+/// `tx`. Replaces the old model where each SSE handler ran its own
+/// independent `tokio::time::interval`; one instance of this runs per entry
+/// in [`KNOWN_TOPICS`] so topics tick independently of each other.
+async fn run_notification_producer(
+    topic: String,
+    tx: broadcast::Sender<Notification>,
+    buffer: Arc<Mutex<VecDeque<Notification>>>,
+    buffer_capacity: usize,
+) {
+    let mut ticker = interval(Duration::from_secs(2));
+    let mut next_id: u64 = 1;
+    loop {
+        ticker.tick().await;
 
-    Box::pin(stream.map(move |_| {
         let timestamp = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or(Duration::from_secs(0))
             .as_secs();
-        
-        let notification = format!(
-            "data: {{\"message\": \"Notification at {}\", \"timestamp\": {}}}\n\n",
-            chrono::Local::now().format("%H:%M:%S"),
-            timestamp
-        );
-        Ok(web::Bytes::from(notification))
-    }))
+
+        let notification = Notification {
+            id: next_id,
+            message: format!(
+                "{} notification at {}",
+                topic,
+                chrono::Local::now().format("%H:%M:%S")
+            ),
+            timestamp,
+        };
+        next_id += 1;
+
+        if let Ok(mut buffer) = buffer.lock() {
+            buffer.push_back(notification.clone());
+            while buffer.len() > buffer_capacity {
+                buffer.pop_front();
+            }
+        }
+
+        // No receivers is not an error - it just means no client is
+        // currently connected to see this tick.
+        let _ = tx.send(notification);
+    }
+}
+
+/// Renders a notification as the SSE frame format used both for live events
+/// and for [`replay_since`]'s replayed ones: an `id:` line (what a
+/// reconnecting client's `Last-Event-ID` will echo back), an `event:` line
+/// naming the topic it came from (so `addEventListener("orders", ...)` only
+/// fires for `orders` traffic), followed by `data:`.
+fn encode_notification(topic: &str, notification: &Notification) -> web::Bytes {
+    let payload = serde_json::to_string(notification).unwrap_or_default();
+    web::Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", notification.id, topic, payload))
+}
+
+/// Adapts a subscriber's `broadcast::Receiver` into the SSE byte stream
+/// `streaming()` expects. A `Lagged` receiver (the subscriber fell behind
+/// the channel's capacity and missed some notifications) is reported to the
+/// client as a `: lagged` comment line instead of ending the stream - SSE
+/// comments are ignored by `EventSource` but are visible to anyone tailing
+/// the raw response, and the stream keeps going from wherever the channel
+/// picks back up.
+fn notification_stream(
+    topic: String,
+    rx: broadcast::Receiver<Notification>,
+) -> Pin<Box<dyn Stream<Item = Result<web::Bytes, actix_web::Error>>>> {
+    let stream = BroadcastStream::new(rx).map(move |item| match item {
+        Ok(notification) => Ok(encode_notification(&topic, &notification)),
+        Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+            Ok(web::Bytes::from(format!(": lagged, missed {} notifications\n\n", skipped)))
+        }
+    });
+
+    Box::pin(stream)
+}
+
+/// An endless stream of `: ping` comment lines, one per `interval` tick.
+/// Merged into every SSE response alongside the topic streams so a
+/// connection with nothing to say still writes *something* often enough
+/// that neither the client nor an intermediary proxy times it out as dead -
+/// `EventSource` ignores comment lines, so this is invisible to application
+/// code.
+fn heartbeat_stream(
+    interval: Duration,
+) -> Pin<Box<dyn Stream<Item = Result<web::Bytes, actix_web::Error>>>> {
+    let stream = IntervalStream::new(tokio::time::interval(interval))
+        .map(|_| Ok(web::Bytes::from_static(b": ping\n\n")));
+
+    Box::pin(stream)
 }
 
 // Endpoint to check active connections
 async fn connection_status(data: web::Data<AppState>) -> Result<HttpResponse> {
     let connections = data.active_connections.lock()
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Lock error: {}", e)))?;
-    
+
+    // Derived straight from the live connection map, which only ever holds
+    // an entry while its `GuardedStream` is actually open - no separate
+    // monotonic counter to drift out of sync with reality.
+    let connection_ids: Vec<usize> = connections.keys().copied().collect();
+
     let status = serde_json::json!({
         "active_connections": connections.len(),
-        "connection_ids": *connections,
-        "warning": if connections.len() >= 6 { 
-            "⚠️ DANGER: Approaching browser connection limit! App will freeze soon!" 
-        } else { 
-            "OK" 
+        "connection_ids": connection_ids,
+        "max_connections": data.max_connections,
+        "warning": if connections.len() >= 6 {
+            "⚠️ DANGER: Approaching browser connection limit! App will freeze soon!"
+        } else {
+            "OK"
         }
     });
-    
+
     Ok(HttpResponse::Ok().json(status))
 }
 
@@ -341,8 +769,48 @@ async fn serve_index() -> Result<HttpResponse> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let event_buffer_capacity = 128;
+
+    let mut topics = HashMap::new();
+    for &topic in KNOWN_TOPICS {
+        let buffer = Arc::new(Mutex::new(VecDeque::with_capacity(event_buffer_capacity)));
+        let (tx, _rx) = broadcast::channel(100);
+        tokio::spawn(run_notification_producer(
+            topic.to_string(),
+            tx.clone(),
+            buffer.clone(),
+            event_buffer_capacity,
+        ));
+        topics.insert(topic.to_string(), TopicChannel { tx, buffer });
+    }
+
+    let active_connections = Arc::new(Mutex::new(HashMap::new()));
+    let max_connection_lifetime = Duration::from_secs(10 * 60);
+    tokio::spawn(run_connection_sweeper(active_connections.clone(), max_connection_lifetime));
+
+    // One new connection every 2 seconds per IP on average, with a small
+    // burst allowance - see [`client_ip`] for how the key is derived and the
+    // `Jitter` applied in `handle_sse_stream` for why refusals are jittered.
+    let connection_rate_limiter = Arc::new(RateLimiter::keyed(Quota::with_period(
+        Duration::from_secs(2),
+    )
+    .unwrap()
+    .allow_burst(NonZeroU32::new(5).unwrap())));
+
     let app_state = AppState {
-        active_connections: Arc::new(Mutex::new(Vec::new())),
+        active_connections,
+        topics,
+        event_buffer_capacity,
+        max_connections: 1000,
+        max_connection_lifetime,
+        connection_rate_limiter,
+        max_connections_per_ip: 10,
+        ip_connection_counts: Arc::new(Mutex::new(HashMap::new())),
+    };
+
+    let stream_config = StreamConfig {
+        retry_ms: 3_000,
+        heartbeat_interval: Duration::from_secs(15),
     };
 
     println!("🚀 Starting Buggy SSE Notification Server on http://localhost:8080");
@@ -353,11 +821,182 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(app_state.clone()))
+            .app_data(web::Data::new(stream_config))
             .route("/", web::get().to(serve_index))
             .route("/api/notifications", web::get().to(sse_notifications))
+            .route("/api/notifications/{topic}", web::get().to(sse_notifications_by_topic))
             .route("/api/status", web::get().to(connection_status))
     })
     .bind("127.0.0.1:8080")?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    fn test_app_state(max_connections: usize) -> AppState {
+        let mut topics = HashMap::new();
+        let (tx, _rx) = broadcast::channel(16);
+        topics.insert(
+            DEFAULT_TOPIC.to_string(),
+            TopicChannel {
+                tx,
+                buffer: Arc::new(Mutex::new(VecDeque::new())),
+            },
+        );
+
+        AppState {
+            active_connections: Arc::new(Mutex::new(HashMap::new())),
+            topics,
+            event_buffer_capacity: 16,
+            max_connections,
+            max_connection_lifetime: Duration::from_secs(600),
+            connection_rate_limiter: Arc::new(RateLimiter::keyed(
+                Quota::with_period(Duration::from_millis(1))
+                    .unwrap()
+                    .allow_burst(NonZeroU32::new(100).unwrap()),
+            )),
+            max_connections_per_ip: 100,
+            ip_connection_counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_new_connections_once_the_hard_cap_is_reached() {
+        let app_state = test_app_state(1);
+        // Fill the one available slot before the request comes in, the same
+        // way an already-open stream would hold it.
+        app_state.active_connections.lock().unwrap().insert(
+            1,
+            ConnectionMeta {
+                opened_at: Instant::now(),
+                peer_addr: None,
+                close: Arc::new(AtomicBool::new(false)),
+            },
+        );
+        let stream_config = StreamConfig {
+            retry_ms: 1_000,
+            heartbeat_interval: Duration::from_secs(30),
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state))
+                .app_data(web::Data::new(stream_config))
+                .route("/api/notifications", web::get().to(sse_notifications)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/notifications").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn connection_guard_removes_its_entry_and_releases_its_ip_slot_on_drop() {
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let ip_connection_counts = Arc::new(Mutex::new(HashMap::new()));
+        let client_ip = "127.0.0.1".to_string();
+
+        connections.lock().unwrap().insert(
+            7,
+            ConnectionMeta {
+                opened_at: Instant::now(),
+                peer_addr: None,
+                close: Arc::new(AtomicBool::new(false)),
+            },
+        );
+        ip_connection_counts.lock().unwrap().insert(client_ip.clone(), 1);
+
+        let guard = ConnectionGuard {
+            connection_id: 7,
+            connections: connections.clone(),
+            client_ip: client_ip.clone(),
+            ip_connection_counts: ip_connection_counts.clone(),
+        };
+
+        drop(guard);
+
+        assert!(!connections.lock().unwrap().contains_key(&7));
+        assert!(!ip_connection_counts.lock().unwrap().contains_key(&client_ip));
+    }
+
+    fn notification(id: u64) -> Notification {
+        Notification {
+            id,
+            message: format!("message-{}", id),
+            timestamp: id,
+        }
+    }
+
+    fn bytes_to_string(result: &Result<web::Bytes, actix_web::Error>) -> String {
+        String::from_utf8(result.as_ref().unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn replay_since_with_no_header_starts_live_with_no_replay() {
+        let buffer = Mutex::new(VecDeque::from(vec![notification(1), notification(2)]));
+
+        let frames = replay_since(DEFAULT_TOPIC, &buffer, None);
+
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn replay_since_replays_only_what_came_after_last_event_id() {
+        let buffer = Mutex::new(VecDeque::from(vec![
+            notification(1),
+            notification(2),
+            notification(3),
+        ]));
+
+        let frames = replay_since(DEFAULT_TOPIC, &buffer, Some(1));
+
+        assert_eq!(frames.len(), 2);
+        assert!(bytes_to_string(&frames[0]).starts_with("id: 2\n"));
+        assert!(bytes_to_string(&frames[1]).starts_with("id: 3\n"));
+    }
+
+    #[test]
+    fn replay_since_emits_a_resync_event_when_the_buffer_has_a_gap() {
+        // The buffer's oldest entry is id 10, but the client last saw id 1 -
+        // everything between 2 and 9 was already evicted, so there's a gap
+        // the buffer can't fill.
+        let buffer = Mutex::new(VecDeque::from(vec![notification(10), notification(11)]));
+
+        let frames = replay_since(DEFAULT_TOPIC, &buffer, Some(1));
+
+        assert_eq!(frames.len(), 1);
+        let frame = bytes_to_string(&frames[0]);
+        assert!(frame.contains("event: resync"));
+        assert!(frame.contains(DEFAULT_TOPIC));
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_subscription_to_an_unknown_topic() {
+        let app_state = test_app_state(100);
+        let stream_config = StreamConfig {
+            retry_ms: 1_000,
+            heartbeat_interval: Duration::from_secs(30),
+        };
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state))
+                .app_data(web::Data::new(stream_config))
+                .route("/api/notifications", web::get().to(sse_notifications)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/notifications?topics=not-a-real-topic")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,25 +1,23 @@
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use web_sys::console;
 
 /// Multi-Step Form State Fragmentation Module
-/// 
-/// This module demonstrates a critical state management bug in multi-step forms.
-/// The bug: Steps 1-3 use a Global Store, Step 4 uses local state, and Step 5 
-/// tries to pull from the Global Store again, causing data loss when navigating backward.
 ///
-/// ## The Bug Explained:
-/// 1. User fills Steps 1-3 → Data stored in Global Store ✅
-/// 2. User fills Step 4 → Data stored in LOCAL state (NOT in Global Store) ❌
-/// 3. User proceeds to Step 5 → Pulls data from Global Store (Step 4 data is THERE)
-/// 4. User clicks "Back" to Step 4 → Component re-renders with fresh LOCAL state
-/// 5. **BUG**: Step 4 data is WIPED because it was never in the Global Store!
+/// This module used to demonstrate a critical state management bug in multi-step
+/// forms: Steps 1-3 went into a Global Store, Step 4 went into local component
+/// state, and Step 5 pulled from the Global Store again - so navigating back to
+/// Step 4 silently wiped whatever the user had typed there.
 ///
-/// ## Why This Is Difficult:
-/// - The bug only appears when navigating backward
-/// - Data appears to be saved when moving forward
-/// - Different state sources are not immediately obvious
-/// - Users lose their work unexpectedly
+/// ## The Fix:
+/// `CheckoutStore` no longer hardcodes one `Option<T>` field per step. Every
+/// step's data - including Step 4 - is routed through a single typed registry
+/// ([`StepId`] → [`StepData`]) via [`CheckoutStore::save_step`] /
+/// [`CheckoutStore::get_step`] / [`CheckoutStore::is_step_complete`], so a step
+/// can no longer be left out of the store by omission the way Step 4 was. The
+/// old per-step methods (`save_personal_info`, `is_step4_complete`, ...) are
+/// kept as thin wrappers over the registry for callers that already use them.
 
 /// Step data structures
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -200,35 +198,283 @@ impl OrderReview {
     }
 }
 
-/// Global Store for checkout process
-/// BUG: Step 4 data (SpecialInstructions) is NOT stored here!
-/// It uses local component state instead, causing data loss on back navigation
+/// Identifies one step of the checkout flow. Used as the key into
+/// [`CheckoutStore`]'s step registry rather than one `Option<T>` field per
+/// step, so every step (Step 4 included) is stored the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[wasm_bindgen]
+pub enum StepId {
+    PersonalInfo = 1,
+    ShippingAddress = 2,
+    BillingInfo = 3,
+    SpecialInstructions = 4,
+    OrderReview = 5,
+}
+
+impl StepId {
+    /// Every step, in the order they were historically numbered - used as
+    /// the search space for cycle detection and DFS traversal, not as a
+    /// guarantee about navigation order (that's what the dependency graph is
+    /// for).
+    fn all() -> [StepId; 5] {
+        [
+            StepId::PersonalInfo,
+            StepId::ShippingAddress,
+            StepId::BillingInfo,
+            StepId::SpecialInstructions,
+            StepId::OrderReview,
+        ]
+    }
+}
+
+fn step_id_from_u8(n: u8) -> Option<StepId> {
+    match n {
+        1 => Some(StepId::PersonalInfo),
+        2 => Some(StepId::ShippingAddress),
+        3 => Some(StepId::BillingInfo),
+        4 => Some(StepId::SpecialInstructions),
+        5 => Some(StepId::OrderReview),
+        _ => None,
+    }
+}
+
+/// Whether `dependencies` (step id -> its prerequisite step ids) contains a
+/// cycle, via DFS back-edge detection: a node reachable from itself through
+/// its own prerequisite chain. Uses the classic three-color scheme - a node
+/// still `InProgress` when one of its prerequisites' DFS reaches it again is
+/// a back-edge, i.e. a cycle.
+fn dependency_graph_has_cycle(dependencies: &HashMap<StepId, Vec<StepId>>) -> bool {
+    enum Mark {
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        step_id: StepId,
+        dependencies: &HashMap<StepId, Vec<StepId>>,
+        marks: &mut HashMap<StepId, Mark>,
+    ) -> bool {
+        match marks.get(&step_id) {
+            Some(Mark::InProgress) => return true, // back-edge: cycle found
+            Some(Mark::Done) => return false,
+            None => {}
+        }
+        marks.insert(step_id, Mark::InProgress);
+        if let Some(prerequisites) = dependencies.get(&step_id) {
+            for &prerequisite in prerequisites {
+                if visit(prerequisite, dependencies, marks) {
+                    return true;
+                }
+            }
+        }
+        marks.insert(step_id, Mark::Done);
+        false
+    }
+
+    let mut marks = HashMap::new();
+    StepId::all()
+        .iter()
+        .any(|&step_id| visit(step_id, dependencies, &mut marks))
+}
+
+/// The data held for one step in [`CheckoutStore`]'s registry. Every variant
+/// is already `Serialize`/`Deserialize` via its wrapped type, so
+/// [`CheckoutStore::save_step`]/[`CheckoutStore::get_step`] can move a whole
+/// `StepData` across the `wasm_bindgen` boundary uniformly regardless of
+/// which step it came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum StepData {
+    PersonalInfo(PersonalInfo),
+    ShippingAddress(ShippingAddress),
+    BillingInfo(BillingInfo),
+    SpecialInstructions(SpecialInstructions),
+    OrderReview(OrderReview),
+}
+
+/// One structured validation failure: which field, and why - returned to JS
+/// as `{ field, message }` instead of a bare boolean, so a form can point at
+/// exactly what's wrong.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FieldError {
+    field: String,
+    message: String,
+}
+
+/// Validates a single field's raw value, independent of which step or struct
+/// it came from - so e.g. [`NotEmpty`] can be reused across every step that
+/// has a required text field, instead of re-checking `is_empty()` inline
+/// everywhere.
+trait FieldValidator {
+    fn validate(&self, value: &str) -> Option<String>;
+}
+
+struct NotEmpty;
+
+impl FieldValidator for NotEmpty {
+    fn validate(&self, value: &str) -> Option<String> {
+        if value.trim().is_empty() {
+            Some("must not be empty".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+struct EmailFormat;
+
+impl FieldValidator for EmailFormat {
+    fn validate(&self, value: &str) -> Option<String> {
+        let at_count = value.matches('@').count();
+        let valid = at_count == 1
+            && !value.starts_with('@')
+            && !value.ends_with('@')
+            && value.split('@').nth(1).is_some_and(|domain| domain.contains('.'));
+
+        if valid {
+            None
+        } else {
+            Some("must be a valid email address".to_string())
+        }
+    }
+}
+
+/// Luhn checksum, the same mod-10 check card networks use to catch typos in
+/// a card number - not a guarantee the card exists or is authorized.
+struct LuhnCheck;
+
+impl FieldValidator for LuhnCheck {
+    fn validate(&self, value: &str) -> Option<String> {
+        let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+        if digits.is_empty() {
+            return Some("must contain a card number".to_string());
+        }
+
+        let sum: u32 = digits
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, &d)| {
+                if i % 2 == 1 {
+                    let doubled = d * 2;
+                    if doubled > 9 { doubled - 9 } else { doubled }
+                } else {
+                    d
+                }
+            })
+            .sum();
+
+        if sum % 10 == 0 {
+            None
+        } else {
+            Some("failed Luhn checksum".to_string())
+        }
+    }
+}
+
+fn validate_personal_info(info: &PersonalInfo) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(message) = NotEmpty.validate(&info.first_name) {
+        errors.push(FieldError { field: "first_name".to_string(), message });
+    }
+    if let Some(message) = NotEmpty.validate(&info.last_name) {
+        errors.push(FieldError { field: "last_name".to_string(), message });
+    }
+    if let Some(message) = EmailFormat.validate(&info.email) {
+        errors.push(FieldError { field: "email".to_string(), message });
+    }
+    errors
+}
+
+fn validate_shipping_address(address: &ShippingAddress) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(message) = NotEmpty.validate(&address.postal_code) {
+        errors.push(FieldError { field: "postal_code".to_string(), message });
+    }
+    errors
+}
+
+fn validate_billing_info(billing: &BillingInfo) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if let Some(message) = LuhnCheck.validate(&billing.card_number) {
+        errors.push(FieldError { field: "card_number".to_string(), message });
+    }
+    errors
+}
+
+/// Dispatches to the validators registered for whichever step `data` holds -
+/// Steps that have nothing worth validating (Special Instructions, Order
+/// Review) simply return no errors.
+fn validate_step_data(data: &StepData) -> Vec<FieldError> {
+    match data {
+        StepData::PersonalInfo(info) => validate_personal_info(info),
+        StepData::ShippingAddress(address) => validate_shipping_address(address),
+        StepData::BillingInfo(billing) => validate_billing_info(billing),
+        StepData::SpecialInstructions(_) => Vec::new(),
+        StepData::OrderReview(_) => Vec::new(),
+    }
+}
+
+/// Global Store for checkout process.
+///
+/// Every step's data lives in `steps`, keyed by [`StepId`] - there is no
+/// longer a separate `Option<T>` field per step for a step to fall out of by
+/// omission the way Step 4 used to.
+#[derive(Serialize, Deserialize)]
 #[wasm_bindgen]
 pub struct CheckoutStore {
     current_step: u8,
-    personal_info: Option<PersonalInfo>,
-    shipping_address: Option<ShippingAddress>,
-    billing_info: Option<BillingInfo>,
-    // NOTICE: special_instructions is MISSING from the store!
-    // This is intentional - it simulates the bug where Step 4 uses local state
-    order_review: Option<OrderReview>,
+    steps: HashMap<StepId, StepData>,
+    /// Adjacency list of step id -> the step ids it requires to be complete
+    /// first, e.g. `BillingInfo -> [ShippingAddress]`. Checked for cycles at
+    /// construction and on every [`CheckoutStore::add_dependency`] call, so
+    /// navigation logic never has to worry about looping forever.
+    dependencies: HashMap<StepId, Vec<StepId>>,
+    /// Serialized snapshots taken just before each mutation, most recent
+    /// last - time-travel history, not checkout data, so it's excluded from
+    /// [`CheckoutStore::export_state`]/[`CheckoutStore::hydrate`].
+    #[serde(skip, default)]
+    undo_stack: Vec<String>,
+    /// Snapshots popped off `undo_stack` by [`CheckoutStore::undo`], in case
+    /// the user wants them back via [`CheckoutStore::redo`]. Cleared on any
+    /// new mutation, same as every other undo/redo implementation.
+    #[serde(skip, default)]
+    redo_stack: Vec<String>,
+    #[serde(skip, default = "default_max_history")]
+    max_history: usize,
+}
+
+fn default_max_history() -> usize {
+    50
 }
 
 #[wasm_bindgen]
 impl CheckoutStore {
-    /// Create a new checkout store
+    /// Create a new checkout store, with the default dependency graph
+    /// mirroring the original fixed 1→2→3→4→5 flow: each step requires the
+    /// one before it.
     #[wasm_bindgen(constructor)]
     pub fn new() -> CheckoutStore {
         console::log_1(&JsValue::from_str(
-            "✅ Global Checkout Store initialized (Steps 1-3, 5)"
+            "✅ Global Checkout Store initialized (Steps 1-5)"
         ));
+
+        let mut dependencies = HashMap::new();
+        dependencies.insert(StepId::ShippingAddress, vec![StepId::PersonalInfo]);
+        dependencies.insert(StepId::BillingInfo, vec![StepId::ShippingAddress]);
+        dependencies.insert(StepId::SpecialInstructions, vec![StepId::BillingInfo]);
+        dependencies.insert(StepId::OrderReview, vec![StepId::SpecialInstructions]);
+        debug_assert!(
+            !dependency_graph_has_cycle(&dependencies),
+            "default step dependency graph must not contain cycles"
+        );
+
         CheckoutStore {
             current_step: 1,
-            personal_info: None,
-            shipping_address: None,
-            billing_info: None,
-            // special_instructions is NOT in the global store!
-            order_review: None,
+            steps: HashMap::new(),
+            dependencies,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            max_history: default_max_history(),
         }
     }
 
@@ -238,39 +484,315 @@ impl CheckoutStore {
         self.current_step
     }
 
-    /// Navigate to next step
+    /// Caps how many snapshots [`CheckoutStore::undo`] can reach back
+    /// through, trimming the oldest ones if the history is already over the
+    /// new limit.
     #[wasm_bindgen]
-    pub fn next_step(&mut self) {
-        if self.current_step < 5 {
-            self.current_step += 1;
-            console::log_1(&JsValue::from_str(&format!(
-                "➡️ Navigating to Step {}", self.current_step
+    pub fn set_max_history(&mut self, max_history: usize) {
+        self.max_history = max_history;
+        while self.undo_stack.len() > self.max_history {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// How many steps back [`CheckoutStore::undo`] can currently go - for a
+    /// UI to grey out the undo button once this hits zero.
+    #[wasm_bindgen]
+    pub fn history_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+    /// Pushes a snapshot of the store as it is *before* a mutation, for
+    /// [`CheckoutStore::undo`] to restore later, and clears `redo_stack` -
+    /// same as every other undo/redo implementation, a fresh edit invalidates
+    /// whatever redo history existed.
+    fn snapshot_for_undo(&mut self) {
+        let snapshot = self.export_state();
+        self.undo_stack.push(snapshot);
+        while self.undo_stack.len() > self.max_history {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Overwrites this store's checkout data (steps, current step,
+    /// dependency graph) from a JSON snapshot, without touching the
+    /// undo/redo stacks themselves.
+    fn restore_snapshot(&mut self, json: &str) -> Result<(), JsValue> {
+        let restored: CheckoutStore = serde_json::from_str(json)
+            .map_err(|e| JsValue::from_str(&format!("failed to restore snapshot: {e}")))?;
+        self.current_step = restored.current_step;
+        self.steps = restored.steps;
+        self.dependencies = restored.dependencies;
+        Ok(())
+    }
+
+    /// Moves one snapshot from the undo stack back into the store, pushing
+    /// the store's current state onto the redo stack first.
+    #[wasm_bindgen]
+    pub fn undo(&mut self) -> Result<(), JsValue> {
+        let previous = self.undo_stack.pop().ok_or_else(|| JsValue::from_str("nothing to undo"))?;
+        let current = self.export_state();
+        self.redo_stack.push(current);
+        self.restore_snapshot(&previous)
+    }
+
+    /// Moves one snapshot from the redo stack back into the store, pushing
+    /// the store's current state onto the undo stack first.
+    #[wasm_bindgen]
+    pub fn redo(&mut self) -> Result<(), JsValue> {
+        let next = self.redo_stack.pop().ok_or_else(|| JsValue::from_str("nothing to redo"))?;
+        let current = self.export_state();
+        self.undo_stack.push(current);
+        self.restore_snapshot(&next)
+    }
+
+    /// Serializes the whole store - every step's data, `current_step`, and
+    /// the dependency graph - to JSON, so it can be written to
+    /// `localStorage` and survive a reload instead of resetting to empty.
+    #[wasm_bindgen]
+    pub fn export_state(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Rebuilds a `CheckoutStore` from JSON produced by
+    /// [`CheckoutStore::export_state`].
+    #[wasm_bindgen]
+    pub fn hydrate(json: &str) -> Result<CheckoutStore, JsValue> {
+        serde_json::from_str(json).map_err(|e| JsValue::from_str(&format!("failed to hydrate checkout store: {e}")))
+    }
+
+    /// Declares that `step_id` requires `prerequisite` to be complete before
+    /// it can be entered - see [`CheckoutStore::can_enter`]. Rejected with an
+    /// error if adding it would create a cycle in the dependency graph.
+    #[wasm_bindgen]
+    pub fn add_dependency(&mut self, step_id: StepId, prerequisite: StepId) -> Result<(), JsValue> {
+        let mut candidate = self.dependencies.clone();
+        candidate.entry(step_id).or_insert_with(Vec::new).push(prerequisite);
+
+        if dependency_graph_has_cycle(&candidate) {
+            return Err(JsValue::from_str(&format!(
+                "adding {:?} as a prerequisite of {:?} would create a cycle",
+                prerequisite, step_id
             )));
         }
+
+        self.snapshot_for_undo();
+        self.dependencies = candidate;
+        Ok(())
+    }
+
+    /// The step ids that must be complete before `step_id` can be entered.
+    fn prerequisites(&self, step_id: StepId) -> &[StepId] {
+        self.dependencies.get(&step_id).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
+    /// True only if every prerequisite of `step_id` is already complete.
+    #[wasm_bindgen]
+    pub fn can_enter(&self, step_id: StepId) -> bool {
+        self.prerequisites(step_id)
+            .iter()
+            .all(|&prerequisite| self.is_step_complete(prerequisite))
+    }
+
+    /// Every step reachable right now - complete steps, plus the first
+    /// incomplete step on each branch whose prerequisites are satisfied -
+    /// for rendering a dynamic progress bar rather than a fixed 1..5 list.
+    #[wasm_bindgen]
+    pub fn reachable_steps(&self) -> Vec<u8> {
+        StepId::all()
+            .iter()
+            .filter(|&&step_id| self.is_step_complete(step_id) || self.can_enter(step_id))
+            .map(|&step_id| step_id as u8)
+            .collect()
+    }
+
+    /// Finds the first incomplete-but-enterable step reachable via DFS from
+    /// `StepId::PersonalInfo`, skipping steps that are already complete (and
+    /// therefore already visited in the sense that matters for navigation).
+    /// Returns `None` once nothing left is both incomplete and enterable.
+    fn dfs_next_incomplete(&self) -> Option<StepId> {
+        let mut visited = std::collections::HashSet::new();
+        let mut stack = vec![StepId::PersonalInfo];
+
+        while let Some(step_id) = stack.pop() {
+            if !visited.insert(step_id) {
+                continue;
+            }
+
+            if !self.is_step_complete(step_id) {
+                if self.can_enter(step_id) {
+                    return Some(step_id);
+                }
+                continue;
+            }
+
+            // This step is done - push whatever depends on it so the DFS
+            // can keep walking forward through the graph.
+            for &next in StepId::all().iter() {
+                if self.prerequisites(next).contains(&step_id) {
+                    stack.push(next);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Validates that the current step is complete, then advances to the
+    /// first reachable incomplete step found by [`CheckoutStore::dfs_next_incomplete`].
+    /// Replaces the old unconditional `current_step += 1` - a step can no
+    /// longer be entered before its prerequisites are satisfied.
+    #[wasm_bindgen]
+    pub fn advance(&mut self) -> Result<u8, JsValue> {
+        let current = step_id_from_u8(self.current_step)
+            .ok_or_else(|| JsValue::from_str("current step is not a valid step id"))?;
+
+        if !self.is_step_complete(current) {
+            return Err(JsValue::from_str(&format!(
+                "cannot advance past step {:?}: it is not yet complete",
+                current
+            )));
+        }
+
+        if let Some(data) = self.steps.get(&current) {
+            let errors = validate_step_data(data);
+            if !errors.is_empty() {
+                return Err(JsValue::from_str(&format!(
+                    "cannot advance past step {:?}: {} field error(s) remain",
+                    current,
+                    errors.len()
+                )));
+            }
+        }
+
+        match self.dfs_next_incomplete() {
+            Some(next) => {
+                self.snapshot_for_undo();
+                self.current_step = next as u8;
+                console::log_1(&JsValue::from_str(&format!(
+                    "➡️ Navigating to Step {}", self.current_step
+                )));
+                Ok(self.current_step)
+            }
+            None => Err(JsValue::from_str("no further steps are reachable")),
+        }
+    }
+
+    /// Navigate to next step. Thin wrapper over [`CheckoutStore::advance`]
+    /// for callers that don't need its error detail - if the current step
+    /// isn't complete yet, or nothing further is reachable, this is a no-op.
+    #[wasm_bindgen]
+    pub fn next_step(&mut self) {
+        let _ = self.advance();
     }
 
     /// Navigate to previous step
-    /// BUG: When going back to Step 4, local state is reset!
     #[wasm_bindgen]
     pub fn previous_step(&mut self) {
         if self.current_step > 1 {
+            self.snapshot_for_undo();
             self.current_step -= 1;
             console::log_1(&JsValue::from_str(&format!(
                 "⬅️ Navigating back to Step {}", self.current_step
             )));
-            
-            if self.current_step == 4 {
-                console::log_1(&JsValue::from_str(
-                    "⚠️ BUG TRIGGERED! Returning to Step 4 - Local state will be EMPTY!"
-                ));
-            }
         }
     }
 
+    /// Unified save path for every step, including Step 4: routes `value`
+    /// through the same `steps` registry every other step uses, so a step
+    /// can no longer be left out of the store by omission.
+    #[wasm_bindgen]
+    pub fn save_step(&mut self, step_id: StepId, value: JsValue) -> Result<(), JsValue> {
+        let data = match step_id {
+            StepId::PersonalInfo => StepData::PersonalInfo(serde_wasm_bindgen::from_value(value)?),
+            StepId::ShippingAddress => StepData::ShippingAddress(serde_wasm_bindgen::from_value(value)?),
+            StepId::BillingInfo => StepData::BillingInfo(serde_wasm_bindgen::from_value(value)?),
+            StepId::SpecialInstructions => StepData::SpecialInstructions(serde_wasm_bindgen::from_value(value)?),
+            StepId::OrderReview => StepData::OrderReview(serde_wasm_bindgen::from_value(value)?),
+        };
+        // Only snapshot once we know `value` actually converted - a
+        // malformed `value` bails out via `?` above without mutating any
+        // step data, so it shouldn't push a spurious undo snapshot or wipe
+        // the redo stack either.
+        self.snapshot_for_undo();
+        self.steps.insert(step_id, data);
+        console::log_1(&JsValue::from_str(&format!(
+            "✅ Step {:?} saved to GLOBAL STORE", step_id
+        )));
+        Ok(())
+    }
+
+    /// Fetch whatever is stored for `step_id`, or `undefined` if that step
+    /// hasn't been saved yet.
+    #[wasm_bindgen]
+    pub fn get_step(&self, step_id: StepId) -> JsValue {
+        match self.steps.get(&step_id) {
+            Some(data) => serde_wasm_bindgen::to_value(data).unwrap_or(JsValue::UNDEFINED),
+            None => JsValue::UNDEFINED,
+        }
+    }
+
+    /// True once `step_id` has been saved to the registry, for every step
+    /// uniformly - including Step 4.
+    #[wasm_bindgen]
+    pub fn is_step_complete(&self, step_id: StepId) -> bool {
+        self.steps.contains_key(&step_id)
+    }
+
+    /// Runs `step_id`'s field validators against whatever is currently saved
+    /// for it, and returns a JS array of `{ field, message }` errors - empty
+    /// if the step is valid. A step that hasn't been saved yet reports a
+    /// single error rather than being silently treated as valid.
+    #[wasm_bindgen]
+    pub fn validate_step(&self, step_id: StepId) -> JsValue {
+        let errors = match self.steps.get(&step_id) {
+            Some(data) => validate_step_data(data),
+            None => vec![FieldError {
+                field: "*".to_string(),
+                message: "step has not been saved yet".to_string(),
+            }],
+        };
+        serde_wasm_bindgen::to_value(&errors).unwrap_or(JsValue::UNDEFINED)
+    }
+
+    /// Runs every step's validators, for final-submission reporting - each
+    /// error's `field` is prefixed with its step id (e.g.
+    /// `"PersonalInfo.email"`) so [`CheckoutStore::get_checkout_summary`] can
+    /// say precisely what's blocking checkout instead of just
+    /// present/missing.
+    #[wasm_bindgen]
+    pub fn validate_all(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.all_field_errors()).unwrap_or(JsValue::UNDEFINED)
+    }
+
+    fn all_field_errors(&self) -> Vec<FieldError> {
+        StepId::all()
+            .iter()
+            .flat_map(|&step_id| match self.steps.get(&step_id) {
+                Some(data) => validate_step_data(data)
+                    .into_iter()
+                    .map(|error| FieldError {
+                        field: format!("{:?}.{}", step_id, error.field),
+                        message: error.message,
+                    })
+                    .collect::<Vec<_>>(),
+                None => vec![FieldError {
+                    field: format!("{:?}", step_id),
+                    message: "step has not been saved yet".to_string(),
+                }],
+            })
+            .collect()
+    }
+
     /// Step 1: Save personal info to Global Store ✅
     #[wasm_bindgen]
     pub fn save_personal_info(&mut self, first_name: String, last_name: String, email: String) {
-        self.personal_info = Some(PersonalInfo::new(first_name, last_name, email));
+        self.snapshot_for_undo();
+        self.steps.insert(
+            StepId::PersonalInfo,
+            StepData::PersonalInfo(PersonalInfo::new(first_name, last_name, email)),
+        );
         console::log_1(&JsValue::from_str(
             "✅ Step 1: Personal info saved to GLOBAL STORE"
         ));
@@ -279,7 +801,11 @@ impl CheckoutStore {
     /// Step 2: Save shipping address to Global Store ✅
     #[wasm_bindgen]
     pub fn save_shipping_address(&mut self, street: String, city: String, postal_code: String, country: String) {
-        self.shipping_address = Some(ShippingAddress::new(street, city, postal_code, country));
+        self.snapshot_for_undo();
+        self.steps.insert(
+            StepId::ShippingAddress,
+            StepData::ShippingAddress(ShippingAddress::new(street, city, postal_code, country)),
+        );
         console::log_1(&JsValue::from_str(
             "✅ Step 2: Shipping address saved to GLOBAL STORE"
         ));
@@ -288,28 +814,39 @@ impl CheckoutStore {
     /// Step 3: Save billing info to Global Store ✅
     #[wasm_bindgen]
     pub fn save_billing_info(&mut self, card_number: String, expiry: String, cvv: String) {
-        self.billing_info = Some(BillingInfo::new(card_number, expiry, cvv));
+        self.snapshot_for_undo();
+        self.steps.insert(
+            StepId::BillingInfo,
+            StepData::BillingInfo(BillingInfo::new(card_number, expiry, cvv)),
+        );
         console::log_1(&JsValue::from_str(
             "✅ Step 3: Billing info saved to GLOBAL STORE"
         ));
     }
 
-    /// Step 4: Special Instructions - NOT IN GLOBAL STORE!
-    /// BUG: This method exists but is NEVER called in the typical flow
-    /// Step 4 components use local useState instead!
+    /// Step 4: Save special instructions to Global Store ✅
+    /// Thin wrapper over [`CheckoutStore::save_step`] - now actually reaches
+    /// the store, unlike the local-state version this used to sit behind.
     #[wasm_bindgen]
-    pub fn save_special_instructions_to_store(&mut self, _gift_message: String, _delivery_notes: String, _signature_required: bool) {
-        // This method is intentionally unused to simulate the bug
-        // In a real buggy application, developers might forget this method exists
+    pub fn save_special_instructions_to_store(&mut self, gift_message: String, delivery_notes: String, signature_required: bool) {
+        self.snapshot_for_undo();
+        self.steps.insert(
+            StepId::SpecialInstructions,
+            StepData::SpecialInstructions(SpecialInstructions::new(gift_message, delivery_notes, signature_required)),
+        );
         console::log_1(&JsValue::from_str(
-            "⚠️ WARNING: This method should save to Global Store but is NOT called!"
+            "✅ Step 4: Special instructions saved to GLOBAL STORE"
         ));
     }
 
     /// Step 5: Save order review to Global Store ✅
     #[wasm_bindgen]
     pub fn save_order_review(&mut self, terms_accepted: bool, newsletter_signup: bool) {
-        self.order_review = Some(OrderReview::new(terms_accepted, newsletter_signup));
+        self.snapshot_for_undo();
+        self.steps.insert(
+            StepId::OrderReview,
+            StepData::OrderReview(OrderReview::new(terms_accepted, newsletter_signup)),
+        );
         console::log_1(&JsValue::from_str(
             "✅ Step 5: Order review saved to GLOBAL STORE"
         ));
@@ -318,105 +855,98 @@ impl CheckoutStore {
     /// Check if step 1 is complete
     #[wasm_bindgen]
     pub fn is_step1_complete(&self) -> bool {
-        self.personal_info.is_some()
+        self.is_step_complete(StepId::PersonalInfo)
     }
 
     /// Check if step 2 is complete
     #[wasm_bindgen]
     pub fn is_step2_complete(&self) -> bool {
-        self.shipping_address.is_some()
+        self.is_step_complete(StepId::ShippingAddress)
     }
 
     /// Check if step 3 is complete
     #[wasm_bindgen]
     pub fn is_step3_complete(&self) -> bool {
-        self.billing_info.is_some()
+        self.is_step_complete(StepId::BillingInfo)
     }
 
     /// Check if step 4 is complete
-    /// BUG: This always returns false because data is in local state!
     #[wasm_bindgen]
     pub fn is_step4_complete(&self) -> bool {
-        // Step 4 data is NOT in the global store!
-        console::log_1(&JsValue::from_str(
-            "⚠️ BUG: Checking Step 4 completion but data is NOT in Global Store!"
-        ));
-        false // Always false because special_instructions is not stored!
+        self.is_step_complete(StepId::SpecialInstructions)
     }
 
     /// Check if step 5 is complete
     #[wasm_bindgen]
     pub fn is_step5_complete(&self) -> bool {
-        self.order_review.is_some()
+        self.is_step_complete(StepId::OrderReview)
     }
 
-    /// Get all data for final submission
-    /// BUG: Step 4 data is MISSING!
+    /// Get all data for final submission - reports precisely which fields
+    /// (not just which steps) are blocking checkout, via
+    /// [`CheckoutStore::validate_all`], rather than a bare present/missing
+    /// flag per step.
     #[wasm_bindgen]
     pub fn get_checkout_summary(&self) -> String {
-        let summary = format!(
-            r#"{{
-    "step1_personal_info": {},
-    "step2_shipping_address": {},
-    "step3_billing_info": {},
-    "step4_special_instructions": "⚠️ MISSING - NOT IN GLOBAL STORE!",
-    "step5_order_review": {}
-}}"#,
-            if self.personal_info.is_some() { "✅ Present" } else { "❌ Missing" },
-            if self.shipping_address.is_some() { "✅ Present" } else { "❌ Missing" },
-            if self.billing_info.is_some() { "✅ Present" } else { "❌ Missing" },
-            if self.order_review.is_some() { "✅ Present" } else { "❌ Missing" }
-        );
-        
-        console::log_1(&JsValue::from_str(&format!(
-            "🐛 BUG VISIBLE: Checkout summary shows Step 4 data is MISSING!\n{}", summary
-        )));
-        
+        let errors = self.all_field_errors();
+
+        let summary = if errors.is_empty() {
+            "✅ All steps complete and valid".to_string()
+        } else {
+            let lines: Vec<String> = errors
+                .iter()
+                .map(|error| format!("  ❌ {}: {}", error.field, error.message))
+                .collect();
+            format!("{} field error(s) blocking checkout:\n{}", errors.len(), lines.join("\n"))
+        };
+
+        console::log_1(&JsValue::from_str(&format!("Checkout summary:\n{}", summary)));
+
         summary
     }
 
-    /// Simulate what happens when user goes back to Step 4
+    /// Describes how this store used to fragment Step 4 into local
+    /// component state, and how the unified [`StepId`]/[`StepData`]
+    /// registry fixes it.
     #[wasm_bindgen]
     pub fn demonstrate_bug(&self) -> String {
         format!(
-            r#"🐛 STATE FRAGMENTATION BUG DEMONSTRATION
+            r#"STATE FRAGMENTATION - HOW IT USED TO BREAK, AND THE FIX
+
+OLD SCENARIO: User completes all 5 steps, then clicks "Back" from Step 5 to Step 4
 
-SCENARIO: User completes all 5 steps, then clicks "Back" from Step 5 to Step 4
+WHAT USED TO HAPPEN:
+1. Steps 1-3: Data was in the Global Store
+2. Step 4: Data lived in LOCAL COMPONENT STATE instead
+   - Navigating back remounted the Step 4 component
+   - useState reset to its initial empty state
+   - The user's gift message and delivery notes were gone
+3. Step 5: Read from the Global Store, where Step 4 data never was
 
-WHAT HAPPENS:
-1. Steps 1-3: Data is in Global Store ✅
+ROOT CAUSE: Steps 1-3 and 5 used the Global Store (Redux/Zustand); Step 4 used
+local useState, so it fell out of the store by omission.
+
+THE FIX (this module): one registry, `HashMap<StepId, StepData>`, for every
+step - Step 4 saves and reads through it exactly like every other step:
    - Personal Info: {}
    - Shipping Address: {}
    - Billing Info: {}
-
-2. Step 4: Data is in LOCAL COMPONENT STATE ⚠️
-   - Special Instructions: NOT IN GLOBAL STORE
-   - When component re-renders, useState resets to initial empty state
-   - User's carefully written gift message: GONE!
-   - Delivery instructions: GONE!
-
-3. Step 5: Tries to read from Global Store ❌
-   - Order Review: {}
-   - But Step 4 data was never there!
-
-RESULT: User loses all Step 4 data when navigating backward!
-
-ROOT CAUSE: Inconsistent state management
-- Steps 1-3, 5 use Global Store (Redux/Zustand)
-- Step 4 uses local useState
-- Going "Back" causes Step 4 component to remount with fresh useState
-
-FIX: Unify the Source of Truth - store ALL steps in Global Store!"#,
-            if self.personal_info.is_some() { "Present" } else { "Missing" },
-            if self.shipping_address.is_some() { "Present" } else { "Missing" },
-            if self.billing_info.is_some() { "Present" } else { "Missing" },
-            if self.order_review.is_some() { "Present" } else { "Missing" }
+   - Special Instructions: {}
+   - Order Review: {}"#,
+            if self.is_step_complete(StepId::PersonalInfo) { "Present" } else { "Missing" },
+            if self.is_step_complete(StepId::ShippingAddress) { "Present" } else { "Missing" },
+            if self.is_step_complete(StepId::BillingInfo) { "Present" } else { "Missing" },
+            if self.is_step_complete(StepId::SpecialInstructions) { "Present" } else { "Missing" },
+            if self.is_step_complete(StepId::OrderReview) { "Present" } else { "Missing" },
         )
     }
 }
 
-/// Simulates local component state for Step 4
-/// This is where the bug lives - state is NOT in the global store
+/// Simulates local component state for Step 4 - what a `useState` hook in
+/// the component would hold while the user is typing. On its own this still
+/// resets to empty on remount; [`Step4LocalState::to_store`] and
+/// [`Step4LocalState::from_store`] are the bridge that keeps it in sync with
+/// [`CheckoutStore`] so a remount can rehydrate from there instead.
 #[wasm_bindgen]
 pub struct Step4LocalState {
     gift_message: String,
@@ -427,11 +957,10 @@ pub struct Step4LocalState {
 #[wasm_bindgen]
 impl Step4LocalState {
     /// Create new local state (simulates useState initial state)
-    /// BUG: Every time Step 4 component mounts, this creates EMPTY state
     #[wasm_bindgen(constructor)]
     pub fn new() -> Step4LocalState {
         console::log_1(&JsValue::from_str(
-            "⚠️ Step 4 Local State initialized - NOT connected to Global Store!"
+            "Step 4 Local State initialized"
         ));
         Step4LocalState {
             gift_message: String::new(),
@@ -440,31 +969,55 @@ impl Step4LocalState {
         }
     }
 
-    /// Set gift message in local state
+    /// Writes this local state into `store`'s `SpecialInstructions` step -
+    /// the component calls this whenever the user edits a field, so the
+    /// Global Store always has the latest value instead of only the local
+    /// `useState` copy.
+    #[wasm_bindgen]
+    pub fn to_store(&self, store: &mut CheckoutStore) {
+        store.save_special_instructions_to_store(
+            self.gift_message.clone(),
+            self.delivery_notes.clone(),
+            self.signature_required,
+        );
+    }
+
+    /// Rebuilds local state from whatever `store` has saved for
+    /// `SpecialInstructions`, or empty local state if Step 4 hasn't been
+    /// saved yet - this is what the component calls on mount/remount instead
+    /// of always starting from [`Step4LocalState::new`]'s empty defaults.
+    #[wasm_bindgen]
+    pub fn from_store(store: &CheckoutStore) -> Step4LocalState {
+        match store.steps.get(&StepId::SpecialInstructions) {
+            Some(StepData::SpecialInstructions(instructions)) => Step4LocalState {
+                gift_message: instructions.gift_message.clone(),
+                delivery_notes: instructions.delivery_notes.clone(),
+                signature_required: instructions.signature_required,
+            },
+            _ => Step4LocalState::new(),
+        }
+    }
+
+    /// Set gift message in local state. Call [`Step4LocalState::to_store`]
+    /// afterward to push it into the Global Store.
     #[wasm_bindgen]
     pub fn set_gift_message(&mut self, message: String) {
         self.gift_message = message;
-        console::log_1(&JsValue::from_str(
-            "⚠️ Gift message saved to LOCAL STATE (not Global Store!)"
-        ));
     }
 
-    /// Set delivery notes in local state
+    /// Set delivery notes in local state. Call [`Step4LocalState::to_store`]
+    /// afterward to push it into the Global Store.
     #[wasm_bindgen]
     pub fn set_delivery_notes(&mut self, notes: String) {
         self.delivery_notes = notes;
-        console::log_1(&JsValue::from_str(
-            "⚠️ Delivery notes saved to LOCAL STATE (not Global Store!)"
-        ));
     }
 
-    /// Set signature required in local state
+    /// Set signature required in local state. Call
+    /// [`Step4LocalState::to_store`] afterward to push it into the Global
+    /// Store.
     #[wasm_bindgen]
     pub fn set_signature_required(&mut self, required: bool) {
         self.signature_required = required;
-        console::log_1(&JsValue::from_str(
-            "⚠️ Signature requirement saved to LOCAL STATE (not Global Store!)"
-        ));
     }
 
     /// Get gift message
@@ -485,13 +1038,15 @@ impl Step4LocalState {
         self.signature_required
     }
 
-    /// Demonstrate what happens when component remounts
+    /// Describes what used to happen on remount before
+    /// [`Step4LocalState::from_store`] existed - `useState`'s local copy
+    /// always started empty, with nowhere durable to recover it from.
     #[wasm_bindgen]
     pub fn on_component_remount() -> String {
-        console::log_1(&JsValue::from_str(
-            "🔄 Step 4 Component Remounting - Local state resets to EMPTY!"
-        ));
-        String::from("⚠️ COMPONENT REMOUNTED - All local state data LOST!")
+        String::from(
+            "Step 4 component remounted - call Step4LocalState::from_store(&store) \
+             to rehydrate instead of starting from empty local state."
+        )
     }
 }
 
@@ -554,19 +1109,23 @@ mod tests {
 
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
-    fn test_store_missing_step4_data() {
-        let store = CheckoutStore::new();
-        
-        // Step 4 should always report as incomplete in the store
+    fn test_step4_saves_to_store() {
+        let mut store = CheckoutStore::new();
+
+        // Unsaved by default
         assert_eq!(store.is_step4_complete(), false);
+
+        // Now actually reaches the registry, unlike the old local-state version
+        store.save_special_instructions_to_store("Gift wrap".to_string(), "Leave at door".to_string(), true);
+        assert!(store.is_step4_complete());
     }
 
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_checkout_flow() {
         let mut store = CheckoutStore::new();
-        
-        // Fill steps 1-3
+
+        // Fill steps 1-4
         store.save_personal_info(
             "John".to_string(),
             "Doe".to_string(),
@@ -583,44 +1142,162 @@ mod tests {
             "12/25".to_string(),
             "123".to_string()
         );
-        
-        // Steps 1-3 should be complete
+        store.save_special_instructions_to_store(
+            "Gift wrap please".to_string(),
+            "Leave at door".to_string(),
+            false
+        );
+
+        // Steps 1-4 should all be complete, uniformly
         assert!(store.is_step1_complete());
         assert!(store.is_step2_complete());
         assert!(store.is_step3_complete());
-        
-        // Step 4 is never complete because it uses local state
-        assert!(!store.is_step4_complete());
+        assert!(store.is_step4_complete());
     }
 
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_local_state_initialization() {
         let state = Step4LocalState::new();
-        
+
         // Local state should start empty
         assert_eq!(state.gift_message, "");
         assert_eq!(state.delivery_notes, "");
         assert_eq!(state.signature_required, false);
     }
 
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_step4_local_state_survives_remount_via_store() {
+        let mut store = CheckoutStore::new();
+        let mut local = Step4LocalState::new();
+        local.set_gift_message("Happy birthday!".to_string());
+        local.set_delivery_notes("Ring the bell".to_string());
+        local.set_signature_required(true);
+        local.to_store(&mut store);
+
+        // Simulate a remount: a fresh local state, rehydrated from the store
+        // instead of starting empty.
+        let rehydrated = Step4LocalState::from_store(&store);
+        assert_eq!(rehydrated.gift_message(), "Happy birthday!");
+        assert_eq!(rehydrated.delivery_notes(), "Ring the bell");
+        assert!(rehydrated.signature_required());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_export_and_hydrate_round_trip() {
+        let mut store = CheckoutStore::new();
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "john@example.com".to_string());
+        store.save_special_instructions_to_store("Gift wrap".to_string(), "Leave at door".to_string(), true);
+
+        let json = store.export_state();
+        let hydrated = CheckoutStore::hydrate(&json).expect("export_state output must hydrate back");
+
+        assert!(hydrated.is_step1_complete());
+        assert!(hydrated.is_step4_complete());
+        assert_eq!(hydrated.current_step(), store.current_step());
+    }
+
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_step_navigation() {
         let mut store = CheckoutStore::new();
-        
+
+        assert_eq!(store.current_step(), 1);
+
+        // Navigation is gated on completion now - an unfilled step 1 can't
+        // be advanced past.
+        store.next_step();
         assert_eq!(store.current_step(), 1);
-        
+
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "john@example.com".to_string());
         store.next_step();
         assert_eq!(store.current_step(), 2);
-        
+
+        store.save_shipping_address("123 Main St".to_string(), "Springfield".to_string(), "12345".to_string(), "USA".to_string());
         store.next_step();
+        store.save_billing_info("4111-1111-1111-1111".to_string(), "12/25".to_string(), "123".to_string());
         store.next_step();
+        store.save_special_instructions_to_store("Gift wrap".to_string(), "Leave at door".to_string(), false);
         store.next_step();
         assert_eq!(store.current_step(), 5);
-        
-        // Going back from step 5 to step 4 triggers the bug
+
+        // Going back from step 5 to step 4 no longer loses any step 4 data
         store.previous_step();
         assert_eq!(store.current_step(), 4);
     }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_can_enter_respects_prerequisites() {
+        let store = CheckoutStore::new();
+
+        assert!(store.can_enter(StepId::PersonalInfo));
+        assert!(!store.can_enter(StepId::ShippingAddress));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_add_dependency_rejects_cycles() {
+        let mut store = CheckoutStore::new();
+
+        // PersonalInfo already (transitively) requires OrderReview's whole
+        // chain to get there, so requiring OrderReview first would cycle.
+        let result = store.add_dependency(StepId::PersonalInfo, StepId::OrderReview);
+        assert!(result.is_err());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_undo_redo_restores_step_data() {
+        let mut store = CheckoutStore::new();
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "john@example.com".to_string());
+        assert!(store.is_step1_complete());
+
+        store.undo().expect("undo should succeed after a save");
+        assert!(!store.is_step1_complete());
+
+        store.redo().expect("redo should restore the save");
+        assert!(store.is_step1_complete());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_set_max_history_trims_undo_stack() {
+        let mut store = CheckoutStore::new();
+        store.set_max_history(1);
+
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "john@example.com".to_string());
+        store.save_shipping_address("123 Main St".to_string(), "Springfield".to_string(), "12345".to_string(), "USA".to_string());
+
+        assert_eq!(store.history_len(), 1);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_advance_blocked_by_field_errors() {
+        let mut store = CheckoutStore::new();
+
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "not-an-email".to_string());
+        assert!(store.advance().is_err());
+        assert_eq!(store.current_step(), 1);
+
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "john@example.com".to_string());
+        assert!(store.advance().is_ok());
+        assert_eq!(store.current_step(), 2);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_validate_all_reports_missing_and_invalid_fields() {
+        let mut store = CheckoutStore::new();
+        store.save_personal_info("John".to_string(), "Doe".to_string(), "not-an-email".to_string());
+
+        let errors: Vec<serde_json::Value> =
+            serde_wasm_bindgen::from_value(store.validate_all()).expect("validate_all must produce a JS array");
+
+        assert!(errors.iter().any(|e| e["field"] == "PersonalInfo.email"));
+        assert!(errors.iter().any(|e| e["field"] == "ShippingAddress"));
+    }
 }
@@ -1,11 +1,19 @@
 use wasm_bindgen::prelude::*;
-use web_sys::{console, WebGlRenderingContext, WebGlProgram, WebGlShader, WebGlBuffer};
+use wasm_bindgen::JsCast;
+use web_sys::{
+    console, HtmlImageElement, OesVertexArrayObject, OffscreenCanvas, WebGl2RenderingContext,
+    WebGlBuffer, WebGlFramebuffer, WebGlProgram, WebGlRenderingContext, WebGlShader, WebGlTexture,
+    WebGlUniformLocation, WebGlVertexArrayObject,
+};
 use js_sys::Float32Array;
+use nalgebra_glm as glm;
 use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
+use thiserror::Error;
 
 /// The Shadowed Canvas Context Module
-/// 
+///
 /// This module demonstrates a subtle and difficult-to-debug bug in WebGL image processing:
 /// The Rust side caches GL context state (shader programs, texture bindings) for performance,
 /// but the JavaScript side can modify the GL state between WASM calls, causing the cache
@@ -17,23 +25,124 @@ use std::rc::Rc;
 /// 3. WASM assumes its cached state is still valid and skips rebinding
 /// 4. GL renders with wrong shaders/textures, causing visual glitches
 /// 5. Bug only appears after specific JS UI updates, making it non-deterministic
+///
+/// ## Opting out of the bug: `with_validation`
+/// [`ImageProcessor::new`] keeps the fast/unsafe behavior above by default.
+/// [`ImageProcessor::with_validation`] instead asks the GL context what's
+/// *actually* bound (`getParameter(CURRENT_PROGRAM)` and friends) before
+/// every draw and rebinds whenever that disagrees with `GLStateCache` - see
+/// `verify_cache`. That's strictly more `getParameter` round-trips per
+/// frame, so it stays opt-in rather than becoming the default.
+///
+/// ## VAOs and what they do and don't fix:
+/// `render_effect` used to re-query `a_position`/`a_texCoord` locations and
+/// reissue `vertex_attrib_pointer` on every single draw, while
+/// `GLStateCache::current_buffer` made a halfhearted attempt to track which
+/// buffer was bound so JS-side buffer binds could still invalidate it. A
+/// `WebGlVertexArrayObject` (native on WebGL2, or via the
+/// `OES_vertex_array_object` extension on WebGL1) captures attribute
+/// pointers *and* the bound `ARRAY_BUFFER` atomically, so `apply_*` now only
+/// needs a single `bind_vertex_array` call to restore all of that state,
+/// regardless of what JS touched in between. This does **not** fix the
+/// `current_program` caching bug described above - that's a separate cache
+/// entry, still trusted blindly.
 
-/// Enum representing which shader program is active
+/// Enum representing which shader program is active. `pub`/`#[wasm_bindgen]`
+/// so JS callers can build the effect list [`ImageProcessor::apply_chain`]
+/// takes.
+#[wasm_bindgen]
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum ProgramType {
+pub enum ProgramType {
     Grayscale,
     Blur,
     Invert,
 }
 
+/// Which shader stage failed to compile, for [`ImageProcessorError::ShaderCompile`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum ShaderKind {
+    Vertex,
+    Fragment,
+}
+
+impl fmt::Display for ShaderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderKind::Vertex => write!(f, "vertex"),
+            ShaderKind::Fragment => write!(f, "fragment"),
+        }
+    }
+}
+
+/// Structured errors for everything that can fail inside `ImageProcessor`,
+/// replacing the stringly-typed `JsValue::from_str(...)` errors every
+/// fallible method used to return. `From<ImageProcessorError> for JsValue`
+/// is what lets the `#[wasm_bindgen]` surface keep returning plain `JsValue`
+/// while internal helpers deal in a type callers (and tests) can match on.
+#[derive(Debug, Error)]
+pub enum ImageProcessorError {
+    #[error("{kind} shader failed to compile: {log}")]
+    ShaderCompile { kind: ShaderKind, log: String },
+
+    #[error("program failed to link: {0}")]
+    ProgramLink(String),
+
+    #[error("failed to create a GL context")]
+    ContextCreation,
+
+    #[error("failed to create a GL buffer or vertex array object")]
+    BufferCreation,
+
+    #[error("texture upload failed")]
+    TextureUpload,
+
+    #[error("{0:?} program was used before it was initialized")]
+    ProgramNotInitialized(ProgramType),
+
+    #[error("a framebuffer failed to reach FRAMEBUFFER_COMPLETE status")]
+    FramebufferIncomplete,
+
+    #[error("load_image or load_pixels must be called before apply_chain")]
+    NoImageLoaded,
+}
+
+impl From<ImageProcessorError> for JsValue {
+    fn from(err: ImageProcessorError) -> JsValue {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// A linked program plus the `WebGlUniformLocation`s every effect needs,
+/// resolved once at link time instead of via `get_uniform_location` on every
+/// frame (as `apply_blur` used to do for `u_resolution`).
+struct ProgramInfo {
+    program: WebGlProgram,
+    u_image: Option<WebGlUniformLocation>,
+    u_resolution: Option<WebGlUniformLocation>,
+    u_projection: Option<WebGlUniformLocation>,
+    u_model_view: Option<WebGlUniformLocation>,
+}
+
+/// One render target in the ping-pong pair `apply_chain` draws intermediate
+/// passes into: a texture-backed `WebGlFramebuffer` plus the dimensions it
+/// was built at, so `ensure_framebuffers` can tell when it needs to rebuild
+/// both targets for a differently-sized image.
+struct OffscreenTarget {
+    framebuffer: WebGlFramebuffer,
+    texture: WebGlTexture,
+    width: u32,
+    height: u32,
+}
+
 /// Cached WebGL state - THIS IS THE SOURCE OF THE BUG
 /// The cache assumes the GL state hasn't been modified externally
 #[derive(Clone)]
 struct GLStateCache {
     current_program: Option<ProgramType>,  // Cached program type
     current_texture: Option<u32>,          // Cached texture binding
-    current_buffer: Option<u32>,           // Cached buffer binding
+    current_buffer: Option<u32>,           // Cached buffer binding - vestigial now that VAOs capture this, kept for the program-cache bug above
     invert_mode: bool,                     // Whether to invert colors
+    current_framebuffer: Option<u32>,      // Which apply_chain ping-pong target (if any) is bound; None means the default framebuffer
 }
 
 impl GLStateCache {
@@ -43,61 +152,514 @@ impl GLStateCache {
             current_texture: None,
             current_buffer: None,
             invert_mode: false,
+            current_framebuffer: None,
+        }
+    }
+}
+
+const ARRAY_BUFFER: u32 = WebGl2RenderingContext::ARRAY_BUFFER;
+const STATIC_DRAW: u32 = WebGl2RenderingContext::STATIC_DRAW;
+const FLOAT: u32 = WebGl2RenderingContext::FLOAT;
+const TRIANGLE_STRIP: u32 = WebGl2RenderingContext::TRIANGLE_STRIP;
+const VERTEX_SHADER: u32 = WebGl2RenderingContext::VERTEX_SHADER;
+const FRAGMENT_SHADER: u32 = WebGl2RenderingContext::FRAGMENT_SHADER;
+const COMPILE_STATUS: u32 = WebGl2RenderingContext::COMPILE_STATUS;
+const LINK_STATUS: u32 = WebGl2RenderingContext::LINK_STATUS;
+const COLOR_BUFFER_BIT: u32 = WebGl2RenderingContext::COLOR_BUFFER_BIT;
+const TEXTURE0: u32 = WebGl2RenderingContext::TEXTURE0;
+const TEXTURE_2D: u32 = WebGl2RenderingContext::TEXTURE_2D;
+const RGBA: u32 = WebGl2RenderingContext::RGBA;
+const UNSIGNED_BYTE: u32 = WebGl2RenderingContext::UNSIGNED_BYTE;
+const CLAMP_TO_EDGE: u32 = WebGl2RenderingContext::CLAMP_TO_EDGE;
+const LINEAR: u32 = WebGl2RenderingContext::LINEAR;
+const TEXTURE_WRAP_S: u32 = WebGl2RenderingContext::TEXTURE_WRAP_S;
+const TEXTURE_WRAP_T: u32 = WebGl2RenderingContext::TEXTURE_WRAP_T;
+const TEXTURE_MIN_FILTER: u32 = WebGl2RenderingContext::TEXTURE_MIN_FILTER;
+const TEXTURE_MAG_FILTER: u32 = WebGl2RenderingContext::TEXTURE_MAG_FILTER;
+const CURRENT_PROGRAM: u32 = WebGl2RenderingContext::CURRENT_PROGRAM;
+const ARRAY_BUFFER_BINDING: u32 = WebGl2RenderingContext::ARRAY_BUFFER_BINDING;
+const TEXTURE_BINDING_2D: u32 = WebGl2RenderingContext::TEXTURE_BINDING_2D;
+const FRAMEBUFFER_BINDING: u32 = WebGl2RenderingContext::FRAMEBUFFER_BINDING;
+const FRAMEBUFFER: u32 = WebGl2RenderingContext::FRAMEBUFFER;
+const COLOR_ATTACHMENT0: u32 = WebGl2RenderingContext::COLOR_ATTACHMENT0;
+const FRAMEBUFFER_COMPLETE: u32 = WebGl2RenderingContext::FRAMEBUFFER_COMPLETE;
+
+/// The subset of the WebGL API `ImageProcessor` needs, implemented once for
+/// `WebGl2RenderingContext` (native VAOs) and once for a WebGL1 context paired
+/// with the `OES_vertex_array_object` extension (VAOs via vendor-prefixed
+/// methods). Everything else about the two contexts is identical enough that
+/// the rest of this file never needs to know which one it's talking to.
+trait GlContext {
+    fn create_shader(&self, shader_type: u32) -> Option<WebGlShader>;
+    fn shader_source(&self, shader: &WebGlShader, source: &str);
+    fn compile_shader(&self, shader: &WebGlShader);
+    fn get_shader_parameter(&self, shader: &WebGlShader, pname: u32) -> JsValue;
+    fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String>;
+
+    fn create_program(&self) -> Option<WebGlProgram>;
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader);
+    fn link_program(&self, program: &WebGlProgram);
+    fn get_program_parameter(&self, program: &WebGlProgram, pname: u32) -> JsValue;
+    fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String>;
+    fn use_program(&self, program: Option<&WebGlProgram>);
+
+    fn create_buffer(&self) -> Option<WebGlBuffer>;
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>);
+    fn buffer_data_f32(&self, target: u32, data: &[f32], usage: u32);
+
+    fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32;
+    fn enable_vertex_attrib_array(&self, index: u32);
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32);
+
+    fn get_uniform_location(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation>;
+    fn uniform2f(&self, location: Option<&WebGlUniformLocation>, x: f32, y: f32);
+    fn uniform_matrix4fv(&self, location: Option<&WebGlUniformLocation>, transpose: bool, data: &[f32]);
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32);
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32);
+    fn clear(&self, mask: u32);
+
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject>;
+    fn bind_vertex_array(&self, vao: Option<&WebGlVertexArrayObject>);
+
+    fn create_texture(&self) -> Option<WebGlTexture>;
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>);
+    fn active_texture(&self, unit: u32);
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32);
+    fn uniform1i(&self, location: Option<&WebGlUniformLocation>, value: i32);
+    fn tex_image_2d_image(&self, target: u32, level: i32, internal_format: u32, format: u32, data_type: u32, image: &HtmlImageElement) -> Result<(), JsValue>;
+    fn tex_image_2d_pixels(&self, target: u32, level: i32, internal_format: u32, width: i32, height: i32, border: i32, format: u32, data_type: u32, pixels: Option<&[u8]>) -> Result<(), JsValue>;
+
+    /// `getParameter` - the ground truth `verify_cache` checks `GLStateCache`
+    /// against, for whichever `pname` the caller asks about (`CURRENT_PROGRAM`,
+    /// `ARRAY_BUFFER_BINDING`, `TEXTURE_BINDING_2D`, ...).
+    fn get_parameter(&self, pname: u32) -> Result<JsValue, JsValue>;
+
+    fn create_framebuffer(&self) -> Option<WebGlFramebuffer>;
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&WebGlFramebuffer>);
+    fn framebuffer_texture2d(&self, target: u32, attachment: u32, textarget: u32, texture: &WebGlTexture, level: i32);
+    fn check_framebuffer_status(&self, target: u32) -> u32;
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32);
+}
+
+impl GlContext for WebGl2RenderingContext {
+    fn create_shader(&self, shader_type: u32) -> Option<WebGlShader> {
+        WebGl2RenderingContext::create_shader(self, shader_type)
+    }
+    fn shader_source(&self, shader: &WebGlShader, source: &str) {
+        WebGl2RenderingContext::shader_source(self, shader, source)
+    }
+    fn compile_shader(&self, shader: &WebGlShader) {
+        WebGl2RenderingContext::compile_shader(self, shader)
+    }
+    fn get_shader_parameter(&self, shader: &WebGlShader, pname: u32) -> JsValue {
+        WebGl2RenderingContext::get_shader_parameter(self, shader, pname)
+    }
+    fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+        WebGl2RenderingContext::get_shader_info_log(self, shader)
+    }
+
+    fn create_program(&self) -> Option<WebGlProgram> {
+        WebGl2RenderingContext::create_program(self)
+    }
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        WebGl2RenderingContext::attach_shader(self, program, shader)
+    }
+    fn link_program(&self, program: &WebGlProgram) {
+        WebGl2RenderingContext::link_program(self, program)
+    }
+    fn get_program_parameter(&self, program: &WebGlProgram, pname: u32) -> JsValue {
+        WebGl2RenderingContext::get_program_parameter(self, program, pname)
+    }
+    fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+        WebGl2RenderingContext::get_program_info_log(self, program)
+    }
+    fn use_program(&self, program: Option<&WebGlProgram>) {
+        WebGl2RenderingContext::use_program(self, program)
+    }
+
+    fn create_buffer(&self) -> Option<WebGlBuffer> {
+        WebGl2RenderingContext::create_buffer(self)
+    }
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        WebGl2RenderingContext::bind_buffer(self, target, buffer)
+    }
+    fn buffer_data_f32(&self, target: u32, data: &[f32], usage: u32) {
+        unsafe {
+            let view = Float32Array::view(data);
+            WebGl2RenderingContext::buffer_data_with_array_buffer_view(self, target, &view, usage);
         }
     }
+
+    fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+        WebGl2RenderingContext::get_attrib_location(self, program, name)
+    }
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        WebGl2RenderingContext::enable_vertex_attrib_array(self, index)
+    }
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        WebGl2RenderingContext::vertex_attrib_pointer_with_i32(self, index, size, data_type, normalized, stride, offset)
+    }
+
+    fn get_uniform_location(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation> {
+        WebGl2RenderingContext::get_uniform_location(self, program, name)
+    }
+    fn uniform2f(&self, location: Option<&WebGlUniformLocation>, x: f32, y: f32) {
+        WebGl2RenderingContext::uniform2f(self, location, x, y)
+    }
+    fn uniform_matrix4fv(&self, location: Option<&WebGlUniformLocation>, transpose: bool, data: &[f32]) {
+        WebGl2RenderingContext::uniform_matrix4fv_with_f32_array(self, location, transpose, data)
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        WebGl2RenderingContext::draw_arrays(self, mode, first, count)
+    }
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        WebGl2RenderingContext::clear_color(self, r, g, b, a)
+    }
+    fn clear(&self, mask: u32) {
+        WebGl2RenderingContext::clear(self, mask)
+    }
+
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        WebGl2RenderingContext::create_vertex_array(self)
+    }
+    fn bind_vertex_array(&self, vao: Option<&WebGlVertexArrayObject>) {
+        WebGl2RenderingContext::bind_vertex_array(self, vao)
+    }
+
+    fn create_texture(&self) -> Option<WebGlTexture> {
+        WebGl2RenderingContext::create_texture(self)
+    }
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        WebGl2RenderingContext::bind_texture(self, target, texture)
+    }
+    fn active_texture(&self, unit: u32) {
+        WebGl2RenderingContext::active_texture(self, unit)
+    }
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        WebGl2RenderingContext::tex_parameteri(self, target, pname, param)
+    }
+    fn uniform1i(&self, location: Option<&WebGlUniformLocation>, value: i32) {
+        WebGl2RenderingContext::uniform1i(self, location, value)
+    }
+    fn tex_image_2d_image(&self, target: u32, level: i32, internal_format: u32, format: u32, data_type: u32, image: &HtmlImageElement) -> Result<(), JsValue> {
+        WebGl2RenderingContext::tex_image_2d_with_u32_and_u32_and_html_image_element(
+            self, target, level, internal_format as i32, format, data_type, image,
+        )
+    }
+    fn tex_image_2d_pixels(&self, target: u32, level: i32, internal_format: u32, width: i32, height: i32, border: i32, format: u32, data_type: u32, pixels: Option<&[u8]>) -> Result<(), JsValue> {
+        WebGl2RenderingContext::tex_image_2d_with_i32_and_i32_and_i32_and_u32_and_u32_and_opt_u8_array(
+            self, target, level, internal_format as i32, width, height, border, format, data_type, pixels,
+        )
+    }
+
+    fn get_parameter(&self, pname: u32) -> Result<JsValue, JsValue> {
+        WebGl2RenderingContext::get_parameter(self, pname)
+    }
+
+    fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+        WebGl2RenderingContext::create_framebuffer(self)
+    }
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&WebGlFramebuffer>) {
+        WebGl2RenderingContext::bind_framebuffer(self, target, framebuffer)
+    }
+    fn framebuffer_texture2d(&self, target: u32, attachment: u32, textarget: u32, texture: &WebGlTexture, level: i32) {
+        WebGl2RenderingContext::framebuffer_texture_2d(self, target, attachment, textarget, Some(texture), level)
+    }
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        WebGl2RenderingContext::check_framebuffer_status(self, target)
+    }
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        WebGl2RenderingContext::viewport(self, x, y, width, height)
+    }
+}
+
+/// WebGL1 context plus the `OES_vertex_array_object` extension object, used
+/// when the browser doesn't support WebGL2.
+struct WebGl1WithVao(WebGlRenderingContext, OesVertexArrayObject);
+
+impl GlContext for WebGl1WithVao {
+    fn create_shader(&self, shader_type: u32) -> Option<WebGlShader> {
+        self.0.create_shader(shader_type)
+    }
+    fn shader_source(&self, shader: &WebGlShader, source: &str) {
+        self.0.shader_source(shader, source)
+    }
+    fn compile_shader(&self, shader: &WebGlShader) {
+        self.0.compile_shader(shader)
+    }
+    fn get_shader_parameter(&self, shader: &WebGlShader, pname: u32) -> JsValue {
+        self.0.get_shader_parameter(shader, pname)
+    }
+    fn get_shader_info_log(&self, shader: &WebGlShader) -> Option<String> {
+        self.0.get_shader_info_log(shader)
+    }
+
+    fn create_program(&self) -> Option<WebGlProgram> {
+        self.0.create_program()
+    }
+    fn attach_shader(&self, program: &WebGlProgram, shader: &WebGlShader) {
+        self.0.attach_shader(program, shader)
+    }
+    fn link_program(&self, program: &WebGlProgram) {
+        self.0.link_program(program)
+    }
+    fn get_program_parameter(&self, program: &WebGlProgram, pname: u32) -> JsValue {
+        self.0.get_program_parameter(program, pname)
+    }
+    fn get_program_info_log(&self, program: &WebGlProgram) -> Option<String> {
+        self.0.get_program_info_log(program)
+    }
+    fn use_program(&self, program: Option<&WebGlProgram>) {
+        self.0.use_program(program)
+    }
+
+    fn create_buffer(&self) -> Option<WebGlBuffer> {
+        self.0.create_buffer()
+    }
+    fn bind_buffer(&self, target: u32, buffer: Option<&WebGlBuffer>) {
+        self.0.bind_buffer(target, buffer)
+    }
+    fn buffer_data_f32(&self, target: u32, data: &[f32], usage: u32) {
+        unsafe {
+            let view = Float32Array::view(data);
+            self.0.buffer_data_with_array_buffer_view(target, &view, usage);
+        }
+    }
+
+    fn get_attrib_location(&self, program: &WebGlProgram, name: &str) -> i32 {
+        self.0.get_attrib_location(program, name)
+    }
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.0.enable_vertex_attrib_array(index)
+    }
+    fn vertex_attrib_pointer(&self, index: u32, size: i32, data_type: u32, normalized: bool, stride: i32, offset: i32) {
+        self.0.vertex_attrib_pointer_with_i32(index, size, data_type, normalized, stride, offset)
+    }
+
+    fn get_uniform_location(&self, program: &WebGlProgram, name: &str) -> Option<WebGlUniformLocation> {
+        self.0.get_uniform_location(program, name)
+    }
+    fn uniform2f(&self, location: Option<&WebGlUniformLocation>, x: f32, y: f32) {
+        self.0.uniform2f(location, x, y)
+    }
+    fn uniform_matrix4fv(&self, location: Option<&WebGlUniformLocation>, transpose: bool, data: &[f32]) {
+        self.0.uniform_matrix4fv_with_f32_array(location, transpose, data)
+    }
+
+    fn draw_arrays(&self, mode: u32, first: i32, count: i32) {
+        self.0.draw_arrays(mode, first, count)
+    }
+    fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.0.clear_color(r, g, b, a)
+    }
+    fn clear(&self, mask: u32) {
+        self.0.clear(mask)
+    }
+
+    fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+        self.1.create_vertex_array_oes()
+    }
+    fn bind_vertex_array(&self, vao: Option<&WebGlVertexArrayObject>) {
+        self.1.bind_vertex_array_oes(vao)
+    }
+
+    fn create_texture(&self) -> Option<WebGlTexture> {
+        self.0.create_texture()
+    }
+    fn bind_texture(&self, target: u32, texture: Option<&WebGlTexture>) {
+        self.0.bind_texture(target, texture)
+    }
+    fn active_texture(&self, unit: u32) {
+        self.0.active_texture(unit)
+    }
+    fn tex_parameteri(&self, target: u32, pname: u32, param: i32) {
+        self.0.tex_parameteri(target, pname, param)
+    }
+    fn uniform1i(&self, location: Option<&WebGlUniformLocation>, value: i32) {
+        self.0.uniform1i(location, value)
+    }
+    fn tex_image_2d_image(&self, target: u32, level: i32, internal_format: u32, format: u32, data_type: u32, image: &HtmlImageElement) -> Result<(), JsValue> {
+        self.0.tex_image_2d_with_u32_and_u32_and_html_image_element(
+            target, level, internal_format as i32, format, data_type, image,
+        )
+    }
+    fn tex_image_2d_pixels(&self, target: u32, level: i32, internal_format: u32, width: i32, height: i32, border: i32, format: u32, data_type: u32, pixels: Option<&[u8]>) -> Result<(), JsValue> {
+        self.0.tex_image_2d_with_i32_and_i32_and_i32_and_u32_and_u32_and_opt_u8_array(
+            target, level, internal_format as i32, width, height, border, format, data_type, pixels,
+        )
+    }
+
+    fn get_parameter(&self, pname: u32) -> Result<JsValue, JsValue> {
+        self.0.get_parameter(pname)
+    }
+
+    fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+        self.0.create_framebuffer()
+    }
+    fn bind_framebuffer(&self, target: u32, framebuffer: Option<&WebGlFramebuffer>) {
+        self.0.bind_framebuffer(target, framebuffer)
+    }
+    fn framebuffer_texture2d(&self, target: u32, attachment: u32, textarget: u32, texture: &WebGlTexture, level: i32) {
+        self.0.framebuffer_texture_2d(target, attachment, textarget, Some(texture), level)
+    }
+    fn check_framebuffer_status(&self, target: u32) -> u32 {
+        self.0.check_framebuffer_status(target)
+    }
+    fn viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        self.0.viewport(x, y, width, height)
+    }
+}
+
+/// Wrap whatever GL context the caller handed in, preferring WebGL2 (native
+/// VAOs) and falling back to WebGL1 + `OES_vertex_array_object`.
+fn wrap_context(context: JsValue) -> Result<Box<dyn GlContext>, ImageProcessorError> {
+    if let Ok(gl2) = context.clone().dyn_into::<WebGl2RenderingContext>() {
+        console::log_1(&JsValue::from_str("Using WebGL2 context (native VAOs)"));
+        return Ok(Box::new(gl2));
+    }
+
+    let gl1 = context
+        .dyn_into::<WebGlRenderingContext>()
+        .map_err(|_| ImageProcessorError::ContextCreation)?;
+
+    let vao_ext = gl1
+        .get_extension("OES_vertex_array_object")
+        .map_err(|_| ImageProcessorError::ContextCreation)?
+        .ok_or(ImageProcessorError::ContextCreation)?
+        .dyn_into::<OesVertexArrayObject>()
+        .map_err(|_| ImageProcessorError::ContextCreation)?;
+
+    console::log_1(&JsValue::from_str("WebGL2 unavailable; falling back to WebGL1 + OES_vertex_array_object"));
+    Ok(Box::new(WebGl1WithVao(gl1, vao_ext)))
 }
 
 #[wasm_bindgen]
 pub struct ImageProcessor {
-    context: WebGlRenderingContext,
+    context: Box<dyn GlContext>,
     state_cache: Rc<RefCell<GLStateCache>>,
-    grayscale_program: Option<WebGlProgram>,
-    blur_program: Option<WebGlProgram>,
-    invert_program: Option<WebGlProgram>,
+    grayscale_info: Option<ProgramInfo>,
+    blur_info: Option<ProgramInfo>,
+    invert_info: Option<ProgramInfo>,
     vertex_buffer: Option<WebGlBuffer>,
+    grayscale_vao: Option<WebGlVertexArrayObject>,
+    blur_vao: Option<WebGlVertexArrayObject>,
+    invert_vao: Option<WebGlVertexArrayObject>,
+    texture: Option<WebGlTexture>,
     frame_count: u32,
+    /// When set, `ensure_program_bound` calls `verify_cache` to check
+    /// `GLStateCache` against live GL state before trusting a cache hit.
+    /// Off by default - see [`with_validation`](Self::with_validation).
+    validate: bool,
+    /// Column-major `mat4`s uploaded to `u_projection`/`u_modelView` on every
+    /// `render_effect` call. `projection` stays the identity (callers don't
+    /// have a way to change it yet); `model_view` is rebuilt by
+    /// [`set_transform`](Self::set_transform).
+    projection: [f32; 16],
+    model_view: [f32; 16],
+    /// Dimensions of the texture uploaded via `load_image`/`load_pixels`.
+    /// `apply_chain`'s ping-pong framebuffers are sized to match.
+    texture_width: u32,
+    texture_height: u32,
+    /// Lazily built (and rebuilt on a size change) by `ensure_framebuffers`.
+    ping_pong: Option<[OffscreenTarget; 2]>,
 }
 
+const MAT4_IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0,
+];
+
 #[wasm_bindgen]
 impl ImageProcessor {
     /// Creates a new ImageProcessor with the given WebGL context
     /// BUG: Initializes the state cache, which will become stale
+    ///
+    /// `context` may be either a `WebGl2RenderingContext` or a
+    /// `WebGlRenderingContext` (see [`get_webgl_context`], which prefers
+    /// WebGL2 and falls back to WebGL1) - accepting a plain `JsValue` here
+    /// and sniffing the concrete type lets a single constructor serve both.
     #[wasm_bindgen(constructor)]
-    pub fn new(context: WebGlRenderingContext) -> Result<ImageProcessor, JsValue> {
+    pub fn new(context: JsValue) -> Result<ImageProcessor, JsValue> {
+        Self::new_internal(context, false)
+    }
+
+    /// Build an `ImageProcessor` from an `OffscreenCanvas`'s `webgl2`
+    /// context instead of a DOM canvas's, so the whole pipeline can run
+    /// inside a Web Worker. That structurally sidesteps the cache-staleness
+    /// bug this module demonstrates too: main-thread JS UI code has no GL
+    /// context to mutate out from under a worker's own state cache.
+    #[wasm_bindgen]
+    pub fn from_offscreen(canvas: OffscreenCanvas) -> Result<ImageProcessor, JsValue> {
+        let context = canvas
+            .get_context("webgl2")?
+            .ok_or("OffscreenCanvas did not return a webgl2 context")?;
+        Self::new_internal(context.into(), false)
+    }
+
+    /// Same as [`new`](Self::new), but with `validate` set to `true`: every
+    /// `apply_*` call checks live GL state against `GLStateCache` first and
+    /// rebinds on a mismatch instead of trusting a stale cache hit. Slower
+    /// (one or more extra `getParameter` round-trips per draw) but immune to
+    /// the bug this module otherwise demonstrates.
+    #[wasm_bindgen]
+    pub fn with_validation(context: JsValue) -> Result<ImageProcessor, JsValue> {
+        Self::new_internal(context, true)
+    }
+
+    fn new_internal(context: JsValue, validate: bool) -> Result<ImageProcessor, JsValue> {
         console::log_1(&JsValue::from_str("Initializing ImageProcessor with state caching..."));
-        
+
+        let context = wrap_context(context)?;
         let state_cache = Rc::new(RefCell::new(GLStateCache::new()));
-        
+
         let mut processor = ImageProcessor {
             context,
             state_cache,
-            grayscale_program: None,
-            blur_program: None,
-            invert_program: None,
+            grayscale_info: None,
+            blur_info: None,
+            invert_info: None,
             vertex_buffer: None,
+            grayscale_vao: None,
+            blur_vao: None,
+            invert_vao: None,
+            texture: None,
             frame_count: 0,
+            validate,
+            projection: MAT4_IDENTITY,
+            model_view: MAT4_IDENTITY,
+            texture_width: 0,
+            texture_height: 0,
+            ping_pong: None,
         };
-        
+
         processor.initialize_shaders()?;
         processor.initialize_buffers()?;
-        
+
         Ok(processor)
     }
-    
+
     /// Initialize shader programs
     /// BUG: Stores references to programs but doesn't handle external GL state changes
-    fn initialize_shaders(&mut self) -> Result<(), JsValue> {
-        // Vertex shader used by all effects
+    fn initialize_shaders(&mut self) -> Result<(), ImageProcessorError> {
+        // Vertex shader used by all effects. ESSL 1.00 (`attribute`/`varying`)
+        // compiles fine under both WebGL1 and WebGL2 contexts, so there's no
+        // need for a `#version 300 es` variant.
         let vertex_shader_source = r#"
             attribute vec2 a_position;
             attribute vec2 a_texCoord;
+            uniform mat4 u_projection;
+            uniform mat4 u_modelView;
             varying vec2 v_texCoord;
             void main() {
-                gl_Position = vec4(a_position, 0.0, 1.0);
+                gl_Position = u_projection * u_modelView * vec4(a_position, 0.0, 1.0);
                 v_texCoord = a_texCoord;
             }
         "#;
-        
+
         // Grayscale fragment shader
         let grayscale_fragment_source = r#"
             precision mediump float;
@@ -109,7 +671,7 @@ impl ImageProcessor {
                 gl_FragColor = vec4(vec3(gray), color.a);
             }
         "#;
-        
+
         // Blur fragment shader
         let blur_fragment_source = r#"
             precision mediump float;
@@ -131,7 +693,7 @@ impl ImageProcessor {
                 gl_FragColor = color;
             }
         "#;
-        
+
         // Invert fragment shader
         let invert_fragment_source = r#"
             precision mediump float;
@@ -142,212 +704,459 @@ impl ImageProcessor {
                 gl_FragColor = vec4(1.0 - color.rgb, color.a);
             }
         "#;
-        
-        self.grayscale_program = Some(self.create_program(vertex_shader_source, grayscale_fragment_source)?);
-        self.blur_program = Some(self.create_program(vertex_shader_source, blur_fragment_source)?);
-        self.invert_program = Some(self.create_program(vertex_shader_source, invert_fragment_source)?);
-        
+
+        let grayscale_program = self.create_program(vertex_shader_source, grayscale_fragment_source)?;
+        self.grayscale_info = Some(self.build_program_info(grayscale_program));
+        let blur_program = self.create_program(vertex_shader_source, blur_fragment_source)?;
+        self.blur_info = Some(self.build_program_info(blur_program));
+        let invert_program = self.create_program(vertex_shader_source, invert_fragment_source)?;
+        self.invert_info = Some(self.build_program_info(invert_program));
+
         console::log_1(&JsValue::from_str("Shaders initialized"));
         Ok(())
     }
-    
+
+    /// Resolve and cache every uniform location an effect might need, once,
+    /// right after linking - `render_effect` then just reads them back off
+    /// `ProgramInfo` instead of calling `get_uniform_location` per frame.
+    fn build_program_info(&self, program: WebGlProgram) -> ProgramInfo {
+        let u_image = self.context.get_uniform_location(&program, "u_image");
+        let u_resolution = self.context.get_uniform_location(&program, "u_resolution");
+        let u_projection = self.context.get_uniform_location(&program, "u_projection");
+        let u_model_view = self.context.get_uniform_location(&program, "u_modelView");
+        ProgramInfo {
+            program,
+            u_image,
+            u_resolution,
+            u_projection,
+            u_model_view,
+        }
+    }
+
     /// Create a WebGL program from vertex and fragment shader sources
-    fn create_program(&self, vertex_source: &str, fragment_source: &str) -> Result<WebGlProgram, JsValue> {
-        let vertex_shader = self.compile_shader(WebGlRenderingContext::VERTEX_SHADER, vertex_source)?;
-        let fragment_shader = self.compile_shader(WebGlRenderingContext::FRAGMENT_SHADER, fragment_source)?;
-        
+    fn create_program(&self, vertex_source: &str, fragment_source: &str) -> Result<WebGlProgram, ImageProcessorError> {
+        let vertex_shader = self.compile_shader(ShaderKind::Vertex, vertex_source)?;
+        let fragment_shader = self.compile_shader(ShaderKind::Fragment, fragment_source)?;
+
         let program = self.context.create_program()
-            .ok_or_else(|| JsValue::from_str("Failed to create program"))?;
-        
+            .ok_or(ImageProcessorError::ProgramLink("failed to create program object".to_string()))?;
+
         self.context.attach_shader(&program, &vertex_shader);
         self.context.attach_shader(&program, &fragment_shader);
         self.context.link_program(&program);
-        
-        if !self.context.get_program_parameter(&program, WebGlRenderingContext::LINK_STATUS).as_bool().unwrap_or(false) {
+
+        if !self.context.get_program_parameter(&program, LINK_STATUS).as_bool().unwrap_or(false) {
             let info = self.context.get_program_info_log(&program)
                 .unwrap_or_else(|| String::from("Unknown error"));
-            return Err(JsValue::from_str(&format!("Program link error: {}", info)));
+            return Err(ImageProcessorError::ProgramLink(info));
         }
-        
+
         Ok(program)
     }
-    
+
     /// Compile a shader
-    fn compile_shader(&self, shader_type: u32, source: &str) -> Result<WebGlShader, JsValue> {
+    fn compile_shader(&self, kind: ShaderKind, source: &str) -> Result<WebGlShader, ImageProcessorError> {
+        let shader_type = match kind {
+            ShaderKind::Vertex => VERTEX_SHADER,
+            ShaderKind::Fragment => FRAGMENT_SHADER,
+        };
+
         let shader = self.context.create_shader(shader_type)
-            .ok_or_else(|| JsValue::from_str("Failed to create shader"))?;
-        
+            .ok_or_else(|| ImageProcessorError::ShaderCompile { kind, log: "failed to create shader object".to_string() })?;
+
         self.context.shader_source(&shader, source);
         self.context.compile_shader(&shader);
-        
-        if !self.context.get_shader_parameter(&shader, WebGlRenderingContext::COMPILE_STATUS).as_bool().unwrap_or(false) {
-            let info = self.context.get_shader_info_log(&shader)
+
+        if !self.context.get_shader_parameter(&shader, COMPILE_STATUS).as_bool().unwrap_or(false) {
+            let log = self.context.get_shader_info_log(&shader)
                 .unwrap_or_else(|| String::from("Unknown error"));
-            return Err(JsValue::from_str(&format!("Shader compile error: {}", info)));
+            return Err(ImageProcessorError::ShaderCompile { kind, log });
         }
-        
+
         Ok(shader)
     }
-    
-    /// Initialize vertex buffer for rendering
-    fn initialize_buffers(&mut self) -> Result<(), JsValue> {
+
+    /// Initialize the shared vertex buffer and one VAO per program.
+    ///
+    /// Each VAO captures the buffer binding and both attribute pointers at
+    /// the moment it's built, so `render_effect` can restore all of that
+    /// with a single `bind_vertex_array` instead of re-querying attribute
+    /// locations and reissuing `vertex_attrib_pointer` on every frame.
+    fn initialize_buffers(&mut self) -> Result<(), ImageProcessorError> {
         let vertices: [f32; 16] = [
             -1.0, -1.0,  0.0, 0.0,
              1.0, -1.0,  1.0, 0.0,
             -1.0,  1.0,  0.0, 1.0,
              1.0,  1.0,  1.0, 1.0,
         ];
-        
+
         let buffer = self.context.create_buffer()
-            .ok_or_else(|| JsValue::from_str("Failed to create buffer"))?;
-        
-        self.context.bind_buffer(WebGlRenderingContext::ARRAY_BUFFER, Some(&buffer));
-        
-        // BUG: We're caching this buffer binding
-        // Note: In a real implementation, we'd store a proper buffer ID
-        // For this demo, we just mark that *a* buffer is bound
+            .ok_or(ImageProcessorError::BufferCreation)?;
+
+        self.context.bind_buffer(ARRAY_BUFFER, Some(&buffer));
+        self.context.buffer_data_f32(ARRAY_BUFFER, &vertices, STATIC_DRAW);
+
+        // BUG: We're caching this buffer binding; now mostly vestigial since
+        // the VAOs below capture it directly.
         self.state_cache.borrow_mut().current_buffer = Some(1);
-        
-        unsafe {
-            let vertex_array = Float32Array::view(&vertices);
-            self.context.buffer_data_with_array_buffer_view(
-                WebGlRenderingContext::ARRAY_BUFFER,
-                &vertex_array,
-                WebGlRenderingContext::STATIC_DRAW,
-            );
-        }
-        
+
         self.vertex_buffer = Some(buffer);
+
+        let grayscale_program = self.grayscale_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Grayscale))?
+            .program.clone();
+        let blur_program = self.blur_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Blur))?
+            .program.clone();
+        let invert_program = self.invert_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Invert))?
+            .program.clone();
+
+        self.grayscale_vao = Some(self.build_vao(&grayscale_program)?);
+        self.blur_vao = Some(self.build_vao(&blur_program)?);
+        self.invert_vao = Some(self.build_vao(&invert_program)?);
+
         Ok(())
     }
-    
+
+    /// Build and populate a VAO for `program`: bind it, point `a_position`/
+    /// `a_texCoord` at the shared vertex buffer, then unbind so the caller
+    /// starts from a clean slate.
+    fn build_vao(&self, program: &WebGlProgram) -> Result<WebGlVertexArrayObject, ImageProcessorError> {
+        let vao = self.context.create_vertex_array()
+            .ok_or(ImageProcessorError::BufferCreation)?;
+        self.context.bind_vertex_array(Some(&vao));
+
+        // A VAO records the ARRAY_BUFFER binding as part of its state, so
+        // (re)bind the shared buffer before wiring up pointers.
+        self.context.bind_buffer(ARRAY_BUFFER, self.vertex_buffer.as_ref());
+
+        let position_location = self.context.get_attrib_location(program, "a_position") as u32;
+        self.context.enable_vertex_attrib_array(position_location);
+        self.context.vertex_attrib_pointer(position_location, 2, FLOAT, false, 16, 0);
+
+        let texcoord_location = self.context.get_attrib_location(program, "a_texCoord") as u32;
+        self.context.enable_vertex_attrib_array(texcoord_location);
+        self.context.vertex_attrib_pointer(texcoord_location, 2, FLOAT, false, 16, 8);
+
+        self.context.bind_vertex_array(None);
+        Ok(vao)
+    }
+
     /// Apply grayscale effect to the canvas
     /// BUG: Uses cached GL state that may be stale
     #[wasm_bindgen]
     pub fn apply_grayscale(&mut self) -> Result<(), JsValue> {
         self.frame_count += 1;
-        let program = self.grayscale_program.as_ref()
-            .ok_or_else(|| JsValue::from_str("Grayscale program not initialized"))?;
-        
-        // BUG: Check cache instead of always binding
-        // If JS has changed the current program, we won't detect it!
-        let program_type = ProgramType::Grayscale;
-        if self.state_cache.borrow().current_program != Some(program_type) {
-            console::log_1(&JsValue::from_str("Cache miss: binding grayscale program"));
-            self.context.use_program(Some(program));
-            self.state_cache.borrow_mut().current_program = Some(program_type);
-        } else {
-            // BUG: Assumes program is still active - it might not be!
-            console::log_1(&JsValue::from_str("Cache hit: skipping program bind (POTENTIAL BUG!)"));
-        }
-        
-        self.render_effect(program)?;
-        Ok(())
+        let info = self.grayscale_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Grayscale))?;
+
+        self.ensure_program_bound(&info.program, ProgramType::Grayscale)?;
+
+        let vao = self.grayscale_vao.as_ref()
+            .ok_or(ImageProcessorError::BufferCreation)?;
+        self.render_effect(info, vao)
     }
-    
+
     /// Apply blur effect to the canvas
     /// BUG: Uses cached GL state that may be stale
     #[wasm_bindgen]
     pub fn apply_blur(&mut self, width: f32, height: f32) -> Result<(), JsValue> {
         self.frame_count += 1;
-        let program = self.blur_program.as_ref()
-            .ok_or_else(|| JsValue::from_str("Blur program not initialized"))?;
-        
-        // BUG: Same caching issue
-        let program_type = ProgramType::Blur;
-        if self.state_cache.borrow().current_program != Some(program_type) {
-            console::log_1(&JsValue::from_str("Cache miss: binding blur program"));
-            self.context.use_program(Some(program));
-            self.state_cache.borrow_mut().current_program = Some(program_type);
-        } else {
-            console::log_1(&JsValue::from_str("Cache hit: skipping program bind (POTENTIAL BUG!)"));
-        }
-        
-        // Set resolution uniform
-        let resolution_location = self.context.get_uniform_location(program, "u_resolution");
-        self.context.uniform2f(resolution_location.as_ref(), width, height);
-        
-        self.render_effect(program)?;
-        Ok(())
+        let info = self.blur_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Blur))?;
+
+        self.ensure_program_bound(&info.program, ProgramType::Blur)?;
+        self.context.uniform2f(info.u_resolution.as_ref(), width, height);
+
+        let vao = self.blur_vao.as_ref()
+            .ok_or(ImageProcessorError::BufferCreation)?;
+        self.render_effect(info, vao)
     }
-    
+
     /// Apply color inversion effect
     /// BUG: Most susceptible to the cache bug - often called after JS rendering
     #[wasm_bindgen]
     pub fn apply_invert(&mut self) -> Result<(), JsValue> {
         self.frame_count += 1;
-        let program = self.invert_program.as_ref()
-            .ok_or_else(|| JsValue::from_str("Invert program not initialized"))?;
-        
-        // BUG: Cache check - this is where the bug manifests most often
-        let program_type = ProgramType::Invert;
-        let cache = self.state_cache.borrow();
-        
-        // CRITICAL BUG: If JS modified the GL state for UI rendering,
-        // our cache will be wrong but we'll think it's correct!
-        if cache.current_program != Some(program_type) {
-            drop(cache); // Release borrow
-            console::log_1(&JsValue::from_str("Cache miss: binding invert program"));
+        let info = self.invert_info.as_ref()
+            .ok_or(ImageProcessorError::ProgramNotInitialized(ProgramType::Invert))?;
+
+        self.ensure_program_bound(&info.program, ProgramType::Invert)?;
+
+        let vao = self.invert_vao.as_ref()
+            .ok_or(ImageProcessorError::BufferCreation)?;
+        self.render_effect(info, vao)
+    }
+
+    /// Common rendering logic
+    ///
+    /// Binding `vao` restores the attribute pointers and buffer binding that
+    /// were current when it was built via [`build_vao`](Self::build_vao) -
+    /// no re-querying attribute locations or reissuing
+    /// `vertex_attrib_pointer` here, and nothing JS does to `ARRAY_BUFFER`
+    /// between calls survives the rebind. The texture uploaded via
+    /// [`load_image`](Self::load_image)/[`load_pixels`](Self::load_pixels)
+    /// is (re)bound to texture unit 0 on every draw, same as the VAO.
+    fn render_effect(&self, info: &ProgramInfo, vao: &WebGlVertexArrayObject) -> Result<(), JsValue> {
+        self.context.active_texture(TEXTURE0);
+        self.context.bind_texture(TEXTURE_2D, self.texture.as_ref());
+        self.context.uniform1i(info.u_image.as_ref(), 0);
+
+        self.context.uniform_matrix4fv(info.u_projection.as_ref(), false, &self.projection);
+        self.context.uniform_matrix4fv(info.u_model_view.as_ref(), false, &self.model_view);
+
+        self.context.bind_vertex_array(Some(vao));
+        self.context.draw_arrays(TRIANGLE_STRIP, 0, 4);
+        Ok(())
+    }
+
+    /// Rebuild the model-view matrix from `translate`/`rotate_radians`/`scale`
+    /// (applied in that order: scale, then rotate about Z, then translate)
+    /// via `nalgebra_glm` and cache it; the next `apply_*` call uploads it
+    /// through the bound program's `u_modelView` uniform. Lets callers pan,
+    /// zoom, and rotate the processed image instead of always filling the
+    /// canvas with a fixed fullscreen quad.
+    #[wasm_bindgen]
+    pub fn set_transform(&mut self, translate: &[f32], rotate_radians: f32, scale: &[f32]) -> Result<(), JsValue> {
+        if translate.len() != 2 || scale.len() != 2 {
+            return Err(JsValue::from_str("translate and scale must each have 2 elements ([x, y])"));
+        }
+
+        let model_view = glm::Mat4::identity();
+        let model_view = glm::translate(&model_view, &glm::vec3(translate[0], translate[1], 0.0));
+        let model_view = glm::rotate_z(&model_view, rotate_radians);
+        let model_view = glm::scale(&model_view, &glm::vec3(scale[0], scale[1], 1.0));
+
+        self.model_view.copy_from_slice(model_view.as_slice());
+        Ok(())
+    }
+
+    /// Run `effects` in sequence, feeding each one's output into the next:
+    /// every pass but the last renders into one of two ping-pong
+    /// framebuffers (texture-backed, so its output can be sampled as the
+    /// next pass's `u_image`) instead of the default framebuffer, and the
+    /// final pass draws to the screen. Requires [`load_image`](Self::load_image)
+    /// or [`load_pixels`](Self::load_pixels) to have been called first, since
+    /// the framebuffers are sized to match the loaded texture.
+    #[wasm_bindgen]
+    pub fn apply_chain(&mut self, effects: Vec<ProgramType>) -> Result<(), JsValue> {
+        if effects.is_empty() {
+            return Err(JsValue::from_str("apply_chain requires at least one effect"));
+        }
+
+        self.ensure_framebuffers()?;
+        let width = self.texture_width as i32;
+        let height = self.texture_height as i32;
+        let original_texture = self.texture.clone();
+        let mut source_texture = original_texture.clone();
+        let last_index = effects.len() - 1;
+
+        for (i, effect) in effects.iter().enumerate() {
+            self.frame_count += 1;
+            // Sample from whatever the previous pass rendered into.
+            self.texture = source_texture.clone();
+
+            if i == last_index {
+                self.bind_default_framebuffer();
+            } else {
+                let target = &self.ping_pong.as_ref().ok_or(ImageProcessorError::FramebufferIncomplete)?[i % 2];
+                // BUG, same family as elsewhere in this module: bound
+                // defensively every pass rather than trusting a cache hit,
+                // since intermediate passes are exactly what external state
+                // changes would otherwise corrupt.
+                self.context.bind_framebuffer(FRAMEBUFFER, Some(&target.framebuffer));
+                self.context.viewport(0, 0, width, height);
+                self.state_cache.borrow_mut().current_framebuffer = Some((i % 2) as u32 + 1);
+            }
+
+            let (info, vao) = self.program_info_and_vao(*effect)?;
+            self.ensure_program_bound(&info.program, *effect)?;
+            if *effect == ProgramType::Blur {
+                self.context.uniform2f(info.u_resolution.as_ref(), width as f32, height as f32);
+            }
+            self.render_effect(info, vao)?;
+
+            if i != last_index {
+                source_texture = Some(self.ping_pong.as_ref().unwrap()[i % 2].texture.clone());
+            }
+        }
+
+        self.texture = original_texture;
+        Ok(())
+    }
+
+    fn program_info_and_vao(&self, effect: ProgramType) -> Result<(&ProgramInfo, &WebGlVertexArrayObject), ImageProcessorError> {
+        let (info, vao) = match effect {
+            ProgramType::Grayscale => (&self.grayscale_info, &self.grayscale_vao),
+            ProgramType::Blur => (&self.blur_info, &self.blur_vao),
+            ProgramType::Invert => (&self.invert_info, &self.invert_vao),
+        };
+        Ok((
+            info.as_ref().ok_or(ImageProcessorError::ProgramNotInitialized(effect))?,
+            vao.as_ref().ok_or(ImageProcessorError::BufferCreation)?,
+        ))
+    }
+
+    fn bind_default_framebuffer(&self) {
+        self.context.bind_framebuffer(FRAMEBUFFER, None);
+        self.state_cache.borrow_mut().current_framebuffer = None;
+    }
+
+    /// (Re)build the ping-pong framebuffer pair if they don't exist yet or
+    /// were sized for a different texture.
+    fn ensure_framebuffers(&mut self) -> Result<(), ImageProcessorError> {
+        if self.texture_width == 0 || self.texture_height == 0 {
+            return Err(ImageProcessorError::NoImageLoaded);
+        }
+
+        let up_to_date = self.ping_pong.as_ref().is_some_and(|targets| {
+            targets[0].width == self.texture_width && targets[0].height == self.texture_height
+        });
+        if up_to_date {
+            return Ok(());
+        }
+
+        let a = self.build_offscreen_target(self.texture_width, self.texture_height)?;
+        let b = self.build_offscreen_target(self.texture_width, self.texture_height)?;
+        self.ping_pong = Some([a, b]);
+        Ok(())
+    }
+
+    /// Build one texture-backed framebuffer sized `width` x `height`.
+    fn build_offscreen_target(&self, width: u32, height: u32) -> Result<OffscreenTarget, ImageProcessorError> {
+        let texture = self.context.create_texture().ok_or(ImageProcessorError::BufferCreation)?;
+        self.context.bind_texture(TEXTURE_2D, Some(&texture));
+        self.context
+            .tex_image_2d_pixels(TEXTURE_2D, 0, RGBA, width as i32, height as i32, 0, RGBA, UNSIGNED_BYTE, None)
+            .map_err(|_| ImageProcessorError::TextureUpload)?;
+        self.configure_texture_params();
+
+        let framebuffer = self.context.create_framebuffer().ok_or(ImageProcessorError::BufferCreation)?;
+        self.context.bind_framebuffer(FRAMEBUFFER, Some(&framebuffer));
+        self.context.framebuffer_texture2d(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, &texture, 0);
+        if self.context.check_framebuffer_status(FRAMEBUFFER) != FRAMEBUFFER_COMPLETE {
+            return Err(ImageProcessorError::FramebufferIncomplete);
+        }
+        self.context.bind_framebuffer(FRAMEBUFFER, None);
+
+        Ok(OffscreenTarget {
+            framebuffer,
+            texture,
+            width,
+            height,
+        })
+    }
+
+    /// Decide whether `program` needs (re)binding and do it if so.
+    ///
+    /// BUG (default, `validate == false`): trusts `GLStateCache` outright.
+    /// If JS changed the current program between WASM calls, this still
+    /// reports a cache hit and skips the bind.
+    ///
+    /// When `validate` is set, `verify_cache` runs first and corrects the
+    /// cache against live GL state, so the check below ends up comparing
+    /// against truth instead of a potentially stale belief.
+    fn ensure_program_bound(&self, program: &WebGlProgram, program_type: ProgramType) -> Result<(), JsValue> {
+        if self.validate {
+            self.verify_cache(program, program_type)?;
+        }
+
+        if self.state_cache.borrow().current_program != Some(program_type) {
+            console::log_1(&JsValue::from_str("Cache miss: binding program"));
             self.context.use_program(Some(program));
             self.state_cache.borrow_mut().current_program = Some(program_type);
         } else {
-            drop(cache); // Release borrow
-            // BUG: We think the program is active, but JS may have changed it!
-            console::log_1(&JsValue::from_str("Cache hit: skipping bind (HIGH BUG RISK!)"));
+            console::log_1(&JsValue::from_str("Cache hit: skipping program bind"));
         }
-        
-        self.render_effect(program)?;
         Ok(())
     }
-    
-    /// Common rendering logic
-    /// BUG: Assumes all cached state is valid
-    fn render_effect(&self, program: &WebGlProgram) -> Result<(), JsValue> {
-        // Setup attributes
-        let position_location = self.context.get_attrib_location(program, "a_position") as u32;
-        let texcoord_location = self.context.get_attrib_location(program, "a_texCoord") as u32;
-        
-        // BUG: We assume the vertex buffer is still bound from initialization
-        // If JS has bound a different buffer, this will use the wrong data!
-        self.context.enable_vertex_attrib_array(position_location);
-        self.context.vertex_attrib_pointer_with_i32(
-            position_location,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            16,
-            0,
-        );
-        
-        self.context.enable_vertex_attrib_array(texcoord_location);
-        self.context.vertex_attrib_pointer_with_i32(
-            texcoord_location,
-            2,
-            WebGlRenderingContext::FLOAT,
-            false,
-            16,
-            8,
-        );
-        
-        // Draw
-        self.context.draw_arrays(WebGlRenderingContext::TRIANGLE_STRIP, 0, 4);
-        
+
+    /// The fix: ask the GL context what's actually bound right now instead
+    /// of trusting `GLStateCache`, and rebind/update the cache wherever they
+    /// disagree. Only called when `self.validate` is set.
+    fn verify_cache(&self, program: &WebGlProgram, program_type: ProgramType) -> Result<(), JsValue> {
+        if !self.program_is_live(program)? {
+            console::log_1(&JsValue::from_str(
+                "verify_cache: CURRENT_PROGRAM disagrees with the cache (JS rebound it) - rebinding",
+            ));
+            self.context.use_program(Some(program));
+            self.state_cache.borrow_mut().current_program = Some(program_type);
+        }
+
+        if !self.framebuffer_is_live()? {
+            console::log_1(&JsValue::from_str(
+                "verify_cache: FRAMEBUFFER_BINDING disagrees with the cache - rebinding",
+            ));
+            let expected = self.state_cache.borrow().current_framebuffer;
+            match expected.and_then(|slot| self.ping_pong.as_ref().map(|targets| &targets[(slot - 1) as usize])) {
+                Some(target) => self.context.bind_framebuffer(FRAMEBUFFER, Some(&target.framebuffer)),
+                None => self.context.bind_framebuffer(FRAMEBUFFER, None),
+            }
+        }
+
+        if let Some(buffer) = &self.vertex_buffer {
+            if !self.buffer_is_live(buffer)? {
+                console::log_1(&JsValue::from_str(
+                    "verify_cache: ARRAY_BUFFER_BINDING disagrees with the cache - rebinding",
+                ));
+                self.context.bind_buffer(ARRAY_BUFFER, Some(buffer));
+                self.state_cache.borrow_mut().current_buffer = Some(1);
+            }
+        }
+
+        if let Some(texture) = &self.texture {
+            if !self.texture_is_live(texture)? {
+                console::log_1(&JsValue::from_str(
+                    "verify_cache: TEXTURE_BINDING_2D disagrees with the cache - rebinding",
+                ));
+                self.context.bind_texture(TEXTURE_2D, Some(texture));
+                self.state_cache.borrow_mut().current_texture = Some(1);
+            }
+        }
+
         Ok(())
     }
-    
+
+    fn program_is_live(&self, program: &WebGlProgram) -> Result<bool, JsValue> {
+        let live = self.context.get_parameter(CURRENT_PROGRAM)?;
+        Ok(&live == program.as_ref())
+    }
+
+    fn buffer_is_live(&self, buffer: &WebGlBuffer) -> Result<bool, JsValue> {
+        let live = self.context.get_parameter(ARRAY_BUFFER_BINDING)?;
+        Ok(&live == buffer.as_ref())
+    }
+
+    fn texture_is_live(&self, texture: &WebGlTexture) -> Result<bool, JsValue> {
+        let live = self.context.get_parameter(TEXTURE_BINDING_2D)?;
+        Ok(&live == texture.as_ref())
+    }
+
+    /// Whether `FRAMEBUFFER_BINDING` matches whatever `GLStateCache::current_framebuffer`
+    /// claims is bound - `None`/default framebuffer shows up as `null`.
+    fn framebuffer_is_live(&self) -> Result<bool, JsValue> {
+        let live = self.context.get_parameter(FRAMEBUFFER_BINDING)?;
+        let expected = self.state_cache.borrow().current_framebuffer;
+        Ok(match expected.and_then(|slot| self.ping_pong.as_ref().map(|targets| &targets[(slot - 1) as usize])) {
+            Some(target) => &live == target.framebuffer.as_ref(),
+            None => live.is_null(),
+        })
+    }
+
     /// Clear the canvas
     #[wasm_bindgen]
     pub fn clear(&self) {
         self.context.clear_color(0.0, 0.0, 0.0, 1.0);
-        self.context.clear(WebGlRenderingContext::COLOR_BUFFER_BIT);
+        self.context.clear(COLOR_BUFFER_BIT);
     }
-    
+
     /// Get frame count (for debugging)
     #[wasm_bindgen]
     pub fn get_frame_count(&self) -> u32 {
         self.frame_count
     }
-    
+
     /// Force cache invalidation (this would be the fix, but it's not called automatically)
     #[wasm_bindgen]
     pub fn invalidate_cache(&mut self) {
@@ -357,36 +1166,365 @@ impl ImageProcessor {
         cache.current_texture = None;
         cache.current_buffer = None;
     }
+
+    /// Upload `image` as the texture effects sample via `u_image`. Reuses
+    /// the existing `WebGlTexture` on repeated calls so only the pixel data
+    /// is re-uploaded, not the texture object itself.
+    #[wasm_bindgen]
+    pub fn load_image(&mut self, image: &HtmlImageElement) -> Result<(), JsValue> {
+        let texture = self.texture_or_create()?;
+        self.context.bind_texture(TEXTURE_2D, Some(&texture));
+        self.context
+            .tex_image_2d_image(TEXTURE_2D, 0, RGBA, RGBA, UNSIGNED_BYTE, image)
+            .map_err(|_| ImageProcessorError::TextureUpload)?;
+        self.configure_texture_params();
+        self.texture = Some(texture);
+        self.texture_width = image.natural_width();
+        self.texture_height = image.natural_height();
+        // BUG (tracked alongside current_program/current_buffer): nothing
+        // ever checks this against live GL state either.
+        self.state_cache.borrow_mut().current_texture = Some(1);
+        Ok(())
+    }
+
+    /// Upload raw RGBA8 pixel data (`width * height * 4` bytes) as the
+    /// texture, for callers that don't have a decoded `HtmlImageElement`
+    /// handy (e.g. pixels produced entirely in Rust/WASM).
+    #[wasm_bindgen]
+    pub fn load_pixels(&mut self, width: u32, height: u32, data: &[u8]) -> Result<(), JsValue> {
+        if data.len() as u32 != width * height * 4 {
+            return Err(JsValue::from_str("pixel data length must be width * height * 4 (RGBA8)"));
+        }
+
+        let texture = self.texture_or_create()?;
+        self.context.bind_texture(TEXTURE_2D, Some(&texture));
+        self.context
+            .tex_image_2d_pixels(TEXTURE_2D, 0, RGBA, width as i32, height as i32, 0, RGBA, UNSIGNED_BYTE, Some(data))
+            .map_err(|_| ImageProcessorError::TextureUpload)?;
+        self.configure_texture_params();
+        self.texture = Some(texture);
+        self.texture_width = width;
+        self.texture_height = height;
+        self.state_cache.borrow_mut().current_texture = Some(1);
+        Ok(())
+    }
+
+    fn texture_or_create(&self) -> Result<WebGlTexture, ImageProcessorError> {
+        match &self.texture {
+            Some(texture) => Ok(texture.clone()),
+            None => self.context.create_texture().ok_or(ImageProcessorError::TextureUpload),
+        }
+    }
+
+    fn configure_texture_params(&self) {
+        self.context.tex_parameteri(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+        self.context.tex_parameteri(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+        self.context.tex_parameteri(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as i32);
+        self.context.tex_parameteri(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as i32);
+    }
 }
 
-/// Utility function to create a WebGL context from a canvas
+/// Utility function to get a GL context from a canvas, preferring WebGL2
+/// (native VAOs) and falling back to WebGL1 (VAOs via the
+/// `OES_vertex_array_object` extension) when the browser doesn't support it.
+/// The return type is a plain `JsValue` because [`ImageProcessor::new`]
+/// accepts either concrete context type and figures out which one it got.
 #[wasm_bindgen]
-pub fn get_webgl_context(canvas_id: &str) -> Result<WebGlRenderingContext, JsValue> {
+pub fn get_webgl_context(canvas_id: &str) -> Result<JsValue, JsValue> {
     let window = web_sys::window().ok_or("No window")?;
     let document = window.document().ok_or("No document")?;
     let canvas = document.get_element_by_id(canvas_id)
         .ok_or("Canvas not found")?;
     let canvas: web_sys::HtmlCanvasElement = canvas.dyn_into::<web_sys::HtmlCanvasElement>()?;
-    
+
+    if let Some(context) = canvas.get_context("webgl2")? {
+        return Ok(context.into());
+    }
+
     let context = canvas
         .get_context("webgl")?
-        .ok_or("Failed to get WebGL context")?
-        .dyn_into::<WebGlRenderingContext>()?;
-    
-    Ok(context)
+        .ok_or("Failed to get a WebGL context")?;
+
+    Ok(context.into())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
-    // Note: These tests require a browser environment with WebGL support
-    // Run with: wasm-pack test --headless --firefox
-    
+
     #[test]
     fn test_state_cache_creation() {
         let cache = GLStateCache::new();
         assert_eq!(cache.current_program, None);
         assert_eq!(cache.current_texture, None);
     }
+
+    // `GlContext` has no real WebGL dependency - every method is free
+    // functions over opaque handles - so `verify_cache` and `apply_chain`
+    // can be driven by `MockContext` below instead of a real canvas. Still
+    // gated to `wasm32`/`wasm-bindgen-test` rather than plain `#[test]`
+    // since `WebGlProgram` & friends are `wasm_bindgen` externref types that
+    // only exist once a `JsValue` heap is available.
+    // Run with: wasm-pack test --headless --firefox
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+    #[cfg(target_arch = "wasm32")]
+    use std::cell::Cell;
+
+    /// A `GlContext` that tracks bindings as plain Rust state instead of
+    /// talking to a real WebGL driver. Every object handle is a distinct
+    /// `JsValue` number, so identity comparisons like `program_is_live`
+    /// behave the same way they would against a real `WebGlProgram`.
+    #[cfg(target_arch = "wasm32")]
+    struct MockContext {
+        next_id: Cell<f64>,
+        bound_program: RefCell<Option<WebGlProgram>>,
+        bound_buffer: RefCell<Option<WebGlBuffer>>,
+        bound_texture: RefCell<Option<WebGlTexture>>,
+        bound_framebuffer: RefCell<Option<WebGlFramebuffer>>,
+        // Shared with the test via `framebuffer_binds()` so assertions don't
+        // need to downcast `Box<dyn GlContext>` back to `MockContext`.
+        framebuffer_binds: Rc<RefCell<Vec<Option<f64>>>>,
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    impl MockContext {
+        fn new() -> Self {
+            Self::with_framebuffer_log(Rc::new(RefCell::new(Vec::new())))
+        }
+
+        fn with_framebuffer_log(framebuffer_binds: Rc<RefCell<Vec<Option<f64>>>>) -> Self {
+            MockContext {
+                next_id: Cell::new(1.0),
+                bound_program: RefCell::new(None),
+                bound_buffer: RefCell::new(None),
+                bound_texture: RefCell::new(None),
+                bound_framebuffer: RefCell::new(None),
+                framebuffer_binds,
+            }
+        }
+
+        fn next<T: JsCast>(&self) -> T {
+            let id = self.next_id.get();
+            self.next_id.set(id + 1.0);
+            JsValue::from_f64(id).unchecked_into::<T>()
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    impl GlContext for MockContext {
+        fn create_shader(&self, _shader_type: u32) -> Option<WebGlShader> {
+            Some(self.next())
+        }
+        fn shader_source(&self, _shader: &WebGlShader, _source: &str) {}
+        fn compile_shader(&self, _shader: &WebGlShader) {}
+        fn get_shader_parameter(&self, _shader: &WebGlShader, _pname: u32) -> JsValue {
+            JsValue::from_bool(true)
+        }
+        fn get_shader_info_log(&self, _shader: &WebGlShader) -> Option<String> {
+            None
+        }
+
+        fn create_program(&self) -> Option<WebGlProgram> {
+            Some(self.next())
+        }
+        fn attach_shader(&self, _program: &WebGlProgram, _shader: &WebGlShader) {}
+        fn link_program(&self, _program: &WebGlProgram) {}
+        fn get_program_parameter(&self, _program: &WebGlProgram, _pname: u32) -> JsValue {
+            JsValue::from_bool(true)
+        }
+        fn get_program_info_log(&self, _program: &WebGlProgram) -> Option<String> {
+            None
+        }
+        fn use_program(&self, program: Option<&WebGlProgram>) {
+            *self.bound_program.borrow_mut() = program.cloned();
+        }
+
+        fn create_buffer(&self) -> Option<WebGlBuffer> {
+            Some(self.next())
+        }
+        fn bind_buffer(&self, _target: u32, buffer: Option<&WebGlBuffer>) {
+            *self.bound_buffer.borrow_mut() = buffer.cloned();
+        }
+        fn buffer_data_f32(&self, _target: u32, _data: &[f32], _usage: u32) {}
+
+        fn get_attrib_location(&self, _program: &WebGlProgram, _name: &str) -> i32 {
+            0
+        }
+        fn enable_vertex_attrib_array(&self, _index: u32) {}
+        fn vertex_attrib_pointer(&self, _index: u32, _size: i32, _data_type: u32, _normalized: bool, _stride: i32, _offset: i32) {}
+
+        fn get_uniform_location(&self, _program: &WebGlProgram, _name: &str) -> Option<WebGlUniformLocation> {
+            Some(self.next())
+        }
+        fn uniform2f(&self, _location: Option<&WebGlUniformLocation>, _x: f32, _y: f32) {}
+        fn uniform_matrix4fv(&self, _location: Option<&WebGlUniformLocation>, _transpose: bool, _data: &[f32]) {}
+
+        fn draw_arrays(&self, _mode: u32, _first: i32, _count: i32) {}
+        fn clear_color(&self, _r: f32, _g: f32, _b: f32, _a: f32) {}
+        fn clear(&self, _mask: u32) {}
+
+        fn create_vertex_array(&self) -> Option<WebGlVertexArrayObject> {
+            Some(self.next())
+        }
+        fn bind_vertex_array(&self, _vao: Option<&WebGlVertexArrayObject>) {}
+
+        fn create_texture(&self) -> Option<WebGlTexture> {
+            Some(self.next())
+        }
+        fn bind_texture(&self, _target: u32, texture: Option<&WebGlTexture>) {
+            *self.bound_texture.borrow_mut() = texture.cloned();
+        }
+        fn active_texture(&self, _unit: u32) {}
+        fn tex_parameteri(&self, _target: u32, _pname: u32, _param: i32) {}
+        fn uniform1i(&self, _location: Option<&WebGlUniformLocation>, _value: i32) {}
+        fn tex_image_2d_image(
+            &self,
+            _target: u32,
+            _level: i32,
+            _internal_format: u32,
+            _format: u32,
+            _data_type: u32,
+            _image: &HtmlImageElement,
+        ) -> Result<(), JsValue> {
+            Ok(())
+        }
+        fn tex_image_2d_pixels(
+            &self,
+            _target: u32,
+            _level: i32,
+            _internal_format: u32,
+            _width: i32,
+            _height: i32,
+            _border: i32,
+            _format: u32,
+            _data_type: u32,
+            _pixels: Option<&[u8]>,
+        ) -> Result<(), JsValue> {
+            Ok(())
+        }
+
+        fn get_parameter(&self, pname: u32) -> Result<JsValue, JsValue> {
+            let value = match pname {
+                CURRENT_PROGRAM => self.bound_program.borrow().as_ref().map(|p| p.as_ref().clone()),
+                ARRAY_BUFFER_BINDING => self.bound_buffer.borrow().as_ref().map(|b| b.as_ref().clone()),
+                TEXTURE_BINDING_2D => self.bound_texture.borrow().as_ref().map(|t| t.as_ref().clone()),
+                FRAMEBUFFER_BINDING => self.bound_framebuffer.borrow().as_ref().map(|f| f.as_ref().clone()),
+                _ => None,
+            };
+            Ok(value.unwrap_or(JsValue::NULL))
+        }
+
+        fn create_framebuffer(&self) -> Option<WebGlFramebuffer> {
+            Some(self.next())
+        }
+        fn bind_framebuffer(&self, _target: u32, framebuffer: Option<&WebGlFramebuffer>) {
+            let id = framebuffer.and_then(|fb| fb.as_ref().as_f64());
+            self.framebuffer_binds.borrow_mut().push(id);
+            *self.bound_framebuffer.borrow_mut() = framebuffer.cloned();
+        }
+        fn framebuffer_texture2d(&self, _target: u32, _attachment: u32, _textarget: u32, _texture: &WebGlTexture, _level: i32) {}
+        fn check_framebuffer_status(&self, _target: u32) -> u32 {
+            FRAMEBUFFER_COMPLETE
+        }
+        fn viewport(&self, _x: i32, _y: i32, _width: i32, _height: i32) {}
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn processor_with_mock(validate: bool) -> (ImageProcessor, Rc<RefCell<Vec<Option<f64>>>>) {
+        let framebuffer_binds = Rc::new(RefCell::new(Vec::new()));
+        let mut processor = ImageProcessor {
+            context: Box::new(MockContext::with_framebuffer_log(framebuffer_binds.clone())),
+            state_cache: Rc::new(RefCell::new(GLStateCache::new())),
+            grayscale_info: None,
+            blur_info: None,
+            invert_info: None,
+            vertex_buffer: None,
+            grayscale_vao: None,
+            blur_vao: None,
+            invert_vao: None,
+            texture: None,
+            frame_count: 0,
+            validate,
+            projection: MAT4_IDENTITY,
+            model_view: MAT4_IDENTITY,
+            texture_width: 0,
+            texture_height: 0,
+            ping_pong: None,
+        };
+        processor.initialize_shaders().unwrap();
+        processor.initialize_buffers().unwrap();
+        (processor, framebuffer_binds)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn verify_cache_is_a_noop_when_cache_already_matches_live_state() {
+        let (processor, _) = processor_with_mock(true);
+        let program = processor.context.create_program().unwrap();
+        processor.context.use_program(Some(&program));
+        processor.state_cache.borrow_mut().current_program = Some(ProgramType::Invert);
+
+        processor.verify_cache(&program, ProgramType::Invert).unwrap();
+
+        assert_eq!(
+            processor.context.get_parameter(CURRENT_PROGRAM).unwrap(),
+            program.as_ref().clone(),
+        );
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn verify_cache_rebinds_when_live_program_disagrees_with_cache() {
+        let (processor, _) = processor_with_mock(true);
+        let expected = processor.context.create_program().unwrap();
+        let externally_bound = processor.context.create_program().unwrap();
+        // Simulate JS having rebound the program behind our back - the cache
+        // still claims `expected`, but the live context disagrees.
+        processor.context.use_program(Some(&externally_bound));
+        processor.state_cache.borrow_mut().current_program = Some(ProgramType::Invert);
+
+        processor.verify_cache(&expected, ProgramType::Invert).unwrap();
+
+        assert_eq!(
+            processor.context.get_parameter(CURRENT_PROGRAM).unwrap(),
+            expected.as_ref().clone(),
+        );
+        assert_eq!(processor.state_cache.borrow().current_program, Some(ProgramType::Invert));
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn apply_chain_alternates_ping_pong_targets_then_binds_the_default_framebuffer() {
+        let (mut processor, framebuffer_binds) = processor_with_mock(false);
+        processor.load_pixels(2, 2, &[0u8; 16]).unwrap();
+
+        // The first call also lazily builds the ping-pong targets via
+        // `ensure_framebuffers`, which binds each one in turn while setting
+        // it up - warm that up and reset the log so it only reflects the
+        // binds `apply_chain`'s own effect loop makes.
+        processor
+            .apply_chain(vec![ProgramType::Invert])
+            .unwrap();
+        framebuffer_binds.borrow_mut().clear();
+
+        processor
+            .apply_chain(vec![ProgramType::Invert, ProgramType::Grayscale, ProgramType::Blur])
+            .unwrap();
+
+        let binds = framebuffer_binds.borrow();
+
+        // Three effects: the first two passes alternate between the two
+        // ping-pong targets, and the final pass binds the default
+        // framebuffer (`None`) to present the result on screen.
+        assert_eq!(binds.len(), 3);
+        assert!(binds[0].is_some());
+        assert!(binds[1].is_some());
+        assert_ne!(binds[0], binds[1]);
+        assert_eq!(binds[2], None);
+
+        // The cache agrees: after the chain, nothing is bound but the
+        // default framebuffer.
+        assert_eq!(processor.state_cache.borrow().current_framebuffer, None);
+    }
 }
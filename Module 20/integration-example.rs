@@ -7,14 +7,71 @@ use actix_web::{
     web, App, HttpServer, HttpResponse, Responder,
     http::StatusCode,
     middleware::Logger,
+    dev::Payload, Error as ActixError, FromRequest, HttpRequest,
 };
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 
 // Import the buggy session manager
 // In a real project: use session_state_manager::SessionManager;
 // For this example, we'll assume it's available
-use session_state_manager::SessionManager;
+use session_state_manager::{SessionData, SessionManager};
+
+/// Header every handler below authenticates against. The single constant
+/// is what makes this "configurable" - change it here and every
+/// `AuthenticatedSession` extraction follows, same as Databend's
+/// `X-DATABEND-SESSION-ID`.
+const SESSION_HEADER: &str = "X-Session-Id";
+
+/// Extracted, already-validated session. Handlers take this instead of
+/// `web::Path<String>` + a manual `get_session` call, so header parsing,
+/// token verification, and the 401/404 short-circuit live in one place.
+struct AuthenticatedSession {
+    token: String,
+    session: SessionData,
+}
+
+impl std::ops::Deref for AuthenticatedSession {
+    type Target = SessionData;
+
+    fn deref(&self) -> &SessionData {
+        &self.session
+    }
+}
+
+impl FromRequest for AuthenticatedSession {
+    type Error = ActixError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let session_mgr = req
+                .app_data::<web::Data<Arc<SessionManager>>>()
+                .cloned()
+                .ok_or_else(|| actix_web::error::ErrorInternalServerError("session manager not configured"))?;
+
+            let token = req
+                .headers()
+                .get(SESSION_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| actix_web::error::ErrorUnauthorized(format!("missing {} header", SESSION_HEADER)))?
+                .to_string();
+
+            let session = session_mgr
+                .get_session(&token)
+                .await
+                .map_err(actix_web::error::ErrorNotFound)?;
+
+            // BUG: This increment might be lost due to race condition in increment_access()
+            let _ = session_mgr.increment_access(&token).await;
+
+            Ok(AuthenticatedSession { token, session })
+        })
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct LoginRequest {
@@ -25,10 +82,16 @@ struct LoginRequest {
 #[derive(Debug, Serialize)]
 struct LoginResponse {
     success: bool,
-    session_id: Option<String>,
+    session_token: Option<String>,
+    refresh_token: Option<String>,
     message: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
 #[derive(Debug, Serialize)]
 struct SessionInfo {
     username: String,
@@ -63,7 +126,8 @@ async fn login_handler(
     if credentials.password.is_empty() {
         return HttpResponse::Unauthorized().json(LoginResponse {
             success: false,
-            session_id: None,
+            session_token: None,
+            refresh_token: None,
             message: "Invalid credentials".to_string(),
         });
     }
@@ -72,63 +136,77 @@ async fn login_handler(
     // BUG: If multiple requests for the same user arrive simultaneously,
     // multiple sessions might be created when only one should exist
     let user_id = format!("user_{}", credentials.username);
-    
-    match session_mgr.create_session(user_id, credentials.username.clone()) {
-        Ok(session_id) => {
+
+    match session_mgr.create_session(user_id, credentials.username.clone()).await {
+        Ok((session_token, refresh_token)) => {
             HttpResponse::Ok().json(LoginResponse {
                 success: true,
-                session_id: Some(session_id),
+                session_token: Some(session_token),
+                refresh_token: Some(refresh_token),
                 message: "Login successful".to_string(),
             })
         }
         Err(e) => {
             HttpResponse::InternalServerError().json(LoginResponse {
                 success: false,
-                session_id: None,
+                session_token: None,
+                refresh_token: None,
                 message: e,
             })
         }
     }
 }
 
-// Get session info endpoint - retrieves session data
-async fn get_session_handler(
+// Refresh endpoint - redeems a refresh token for a new session+refresh pair
+async fn refresh_handler(
     session_mgr: web::Data<Arc<SessionManager>>,
-    session_id: web::Path<String>,
+    body: web::Json<RefreshRequest>,
 ) -> impl Responder {
-    match session_mgr.get_session(&session_id) {
-        Ok(session) => {
-            // BUG: This increment might be lost due to race condition in increment_access()
-            let _ = session_mgr.increment_access(&session_id);
-            
-            HttpResponse::Ok().json(SessionInfo {
-                username: session.username,
-                login_time: session.login_time.to_string(),
-                access_count: session.access_count,
-                metadata: session.metadata,
+    match session_mgr.refresh(&body.refresh_token).await {
+        Ok((session_token, refresh_token)) => {
+            HttpResponse::Ok().json(LoginResponse {
+                success: true,
+                session_token: Some(session_token),
+                refresh_token: Some(refresh_token),
+                message: "Session refreshed".to_string(),
             })
         }
         Err(e) => {
-            HttpResponse::NotFound().json(serde_json::json!({
-                "error": e
-            }))
+            HttpResponse::Unauthorized().json(LoginResponse {
+                success: false,
+                session_token: None,
+                refresh_token: None,
+                message: e,
+            })
         }
     }
 }
 
+// Get session info endpoint - retrieves session data
+// AuthenticatedSession already did the header parsing, token verification,
+// and access-count bump, so there's nothing left to do but shape the response.
+async fn get_session_handler(session: AuthenticatedSession) -> impl Responder {
+    HttpResponse::Ok().json(SessionInfo {
+        username: session.username.clone(),
+        login_time: session.login_time.to_string(),
+        access_count: session.access_count,
+        metadata: session.metadata.clone(),
+    })
+}
+
 // Update user preference endpoint
 async fn update_preference_handler(
     session_mgr: web::Data<Arc<SessionManager>>,
-    session_id: web::Path<String>,
+    session: AuthenticatedSession,
     preference: web::Json<UpdatePreferenceRequest>,
 ) -> impl Responder {
     // BUG: If multiple preference updates arrive for the same session,
     // some updates might be lost due to the read-modify-write race in update_session()
     match session_mgr.update_session(
-        &session_id,
+        &session.token,
         preference.key.clone(),
         preference.value.clone(),
-    ) {
+    ).await {
         Ok(_) => {
             HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
@@ -146,11 +224,11 @@ async fn update_preference_handler(
 // Logout endpoint - deletes session
 async fn logout_handler(
     session_mgr: web::Data<Arc<SessionManager>>,
-    session_id: web::Path<String>,
+    session: AuthenticatedSession,
 ) -> impl Responder {
     // BUG: During deletion, the session exists in an inconsistent state
     // Statistics might be wrong, cleanup queue might grow unboundedly
-    match session_mgr.delete_session(&session_id) {
+    match session_mgr.delete_session(&session.token).await {
         Ok(_) => {
             HttpResponse::Ok().json(serde_json::json!({
                 "success": true,
@@ -170,7 +248,7 @@ async fn stats_handler(
     session_mgr: web::Data<Arc<SessionManager>>,
 ) -> impl Responder {
     let stats = session_mgr.get_stats();
-    let actual_count = session_mgr.get_active_count();
+    let actual_count = session_mgr.get_active_count().await;
     let queue_size = session_mgr.get_cleanup_queue_size();
     
     // BUG: Due to race conditions, these numbers often don't match
@@ -193,7 +271,7 @@ async fn health_handler(
 ) -> impl Responder {
     let queue_size = session_mgr.get_cleanup_queue_size();
     let stats = session_mgr.get_stats();
-    let actual = session_mgr.get_active_count();
+    let actual = session_mgr.get_active_count().await;
     
     // Detect potential issues
     let memory_leak_warning = queue_size > 1000;
@@ -237,21 +315,23 @@ async fn main() -> std::io::Result<()> {
     println!("⚠️  WARNING: This server uses the buggy session manager!");
     println!();
     println!("Available endpoints:");
-    println!("  POST   /api/login              - Create session");
-    println!("  GET    /api/session/:id        - Get session info");
-    println!("  PUT    /api/session/:id/prefs  - Update preferences");
-    println!("  DELETE /api/logout/:id         - Delete session");
-    println!("  GET    /api/stats              - View statistics");
-    println!("  GET    /health                 - Health check");
-    
+    println!("  POST   /api/login    - Create session");
+    println!("  POST   /api/refresh  - Redeem a refresh token for a new session");
+    println!("  GET    /api/session  - Get session info (needs X-Session-Id header)");
+    println!("  PUT    /api/session/prefs - Update preferences (needs X-Session-Id header)");
+    println!("  DELETE /api/logout   - Delete session (needs X-Session-Id header)");
+    println!("  GET    /api/stats    - View statistics");
+    println!("  GET    /health       - Health check");
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(session_mgr.clone()))
             .wrap(Logger::default())
             .route("/api/login", web::post().to(login_handler))
-            .route("/api/session/{session_id}", web::get().to(get_session_handler))
-            .route("/api/session/{session_id}/prefs", web::put().to(update_preference_handler))
-            .route("/api/logout/{session_id}", web::delete().to(logout_handler))
+            .route("/api/refresh", web::post().to(refresh_handler))
+            .route("/api/session", web::get().to(get_session_handler))
+            .route("/api/session/prefs", web::put().to(update_preference_handler))
+            .route("/api/logout", web::delete().to(logout_handler))
             .route("/api/stats", web::get().to(stats_handler))
             .route("/health", web::get().to(health_handler))
     })
@@ -270,35 +350,41 @@ mod tests {
         let mgr = Arc::new(SessionManager::new());
 
         // 1. User logs in
-        let session_id = mgr
+        let (session_token, refresh_token) = mgr
             .create_session("user_alice".to_string(), "alice".to_string())
+            .await
             .unwrap();
-        println!("Created session: {}", session_id);
+        println!("Created session: {}", session_token);
 
         // 2. User makes some requests (increment access count)
         for i in 0..10 {
-            mgr.increment_access(&session_id).ok();
+            mgr.increment_access(&session_token).await.ok();
             println!("Access #{}", i + 1);
         }
 
         // 3. User updates preferences
-        mgr.update_session(&session_id, "theme".to_string(), "dark".to_string())
+        mgr.update_session(&session_token, "theme".to_string(), "dark".to_string())
+            .await
             .unwrap();
-        mgr.update_session(&session_id, "language".to_string(), "en".to_string())
+        mgr.update_session(&session_token, "language".to_string(), "en".to_string())
+            .await
             .unwrap();
 
         // 4. Get session info
-        let session = mgr.get_session(&session_id).unwrap();
+        let session = mgr.get_session(&session_token).await.unwrap();
         println!("Session info: {:?}", session);
         println!("Metadata: {:?}", session.metadata);
 
         // BUG: Access count might be less than 10 due to race conditions!
         println!("Access count: {} (expected: 10)", session.access_count);
 
-        // 5. User logs out
-        mgr.delete_session(&session_id).unwrap();
+        // 5. Session token expires; redeem the refresh token for a new one
+        let (session_token, _refresh_token) = mgr.refresh(&refresh_token).await.unwrap();
+
+        // 6. User logs out
+        mgr.delete_session(&session_token).await.unwrap();
 
-        // 6. Check statistics
+        // 7. Check statistics
         let stats = mgr.get_stats();
         println!("Stats: {:?}", stats);
 
@@ -308,23 +394,29 @@ mod tests {
 
     #[tokio::test]
     async fn test_concurrent_requests() {
-        let mgr = Arc::new(SessionManager::new());
-        let session_id = mgr
+        // `new_consistent()` retries `update_session` against the store's
+        // compare-and-swap primitive instead of the racy read-modify-write
+        // the default manager uses, so none of the 20 concurrent updates
+        // below should be lost to a lost-update race.
+        let mgr = Arc::new(SessionManager::new_consistent());
+        let (session_token, _refresh_token) = mgr
             .create_session("user_bob".to_string(), "bob".to_string())
+            .await
             .unwrap();
 
         // Simulate concurrent requests from multiple handlers
         let mut handles = vec![];
         for i in 0..20 {
             let mgr = mgr.clone();
-            let sid = session_id.clone();
+            let token = session_token.clone();
             handles.push(tokio::spawn(async move {
                 // Each "request" updates a different preference
                 mgr.update_session(
-                    &sid,
+                    &token,
                     format!("key_{}", i),
                     format!("value_{}", i),
                 )
+                .await
                 .ok();
             }));
         }
@@ -333,12 +425,8 @@ mod tests {
             h.await.unwrap();
         }
 
-        // BUG: Due to race conditions, not all 20 updates will be present!
-        let session = mgr.get_session(&session_id).unwrap();
-        println!(
-            "Expected 20 keys, got: {}",
-            session.metadata.len()
-        );
+        let session = mgr.get_session(&session_token).await.unwrap();
+        assert_eq!(session.metadata.len(), 20, "all 20 concurrent updates should survive");
     }
 }
 
@@ -353,12 +441,27 @@ curl -X POST http://localhost:8080/api/login \
 Response:
 {
   "success": true,
-  "session_id": "550e8400-e29b-41d4-a716-446655440000",
+  "session_token": "s550e8400-e29b-41d4-a716-446655440000",
+  "refresh_token": "r7e5c8b1a-....",
   "message": "Login successful"
 }
 
-# 2. Get session info
-curl http://localhost:8080/api/session/550e8400-e29b-41d4-a716-446655440000
+# 2. Refresh (once the session token expires)
+curl -X POST http://localhost:8080/api/refresh \
+  -H "Content-Type: application/json" \
+  -d '{"refresh_token": "r7e5c8b1a-...."}'
+
+Response:
+{
+  "success": true,
+  "session_token": "s2b6a9f3d-....",
+  "refresh_token": "r91cd04ff-....",
+  "message": "Session refreshed"
+}
+
+# 3. Get session info (AuthenticatedSession reads the session token off this header)
+curl http://localhost:8080/api/session \
+  -H "X-Session-Id: s2b6a9f3d-...."
 
 Response:
 {
@@ -368,8 +471,9 @@ Response:
   "metadata": {}
 }
 
-# 3. Update preference
-curl -X PUT http://localhost:8080/api/session/550e8400-e29b-41d4-a716-446655440000/prefs \
+# 4. Update preference
+curl -X PUT http://localhost:8080/api/session/prefs \
+  -H "X-Session-Id: s2b6a9f3d-...." \
   -H "Content-Type: application/json" \
   -d '{"key": "theme", "value": "dark"}'
 
@@ -379,7 +483,7 @@ Response:
   "message": "Preference updated"
 }
 
-# 4. Check statistics (admin)
+# 5. Check statistics (admin)
 curl http://localhost:8080/api/stats
 
 Response:
@@ -393,8 +497,9 @@ Response:
   "inconsistency_detected": true  # BUG: State is inconsistent!
 }
 
-# 5. Logout
-curl -X DELETE http://localhost:8080/api/logout/550e8400-e29b-41d4-a716-446655440000
+# 6. Logout
+curl -X DELETE http://localhost:8080/api/logout \
+  -H "X-Session-Id: s2b6a9f3d-...."
 
 Response:
 {
@@ -402,7 +507,7 @@ Response:
   "message": "Logged out successfully"
 }
 
-# 6. Health check
+# 7. Health check
 curl http://localhost:8080/health
 
 Response:
@@ -1,8 +1,19 @@
 /*!
 # Session State Manager Module (BUGGY VERSION with Global Lazy)
 
-This module implements a global session state manager using `once_cell::sync::Lazy`.
-It's designed for webapp integration to manage user sessions.
+This module implements a session state manager designed for webapp integration.
+Persistence is pluggable via the [`SessionStore`] trait (`store` module) so the
+same `SessionManager` can run against an in-process `MemoryStore` or a shared
+`RedisStore`/`SqlxStore`; the statistics and cleanup-queue globals below are
+still process-local and still carry the bugs documented below.
+
+`create_session`/`refresh` hand back a `(session_token, refresh_token)` pair:
+the session token is short-lived (see [`SESSION_TOKEN_TTL`]) and is what
+every other method expects, while the refresh token is long-lived and is
+only ever exchanged, via [`SessionManager::refresh`], for a new pair. Both
+are tagged with their [`TokenType`] (`token` module) so one can never be
+presented where the other is expected, and optionally HMAC-signed
+(`new_with_key`) so a forged token is rejected outright.
 
 ## ⚠️ WARNING: This module contains multiple subtle bugs! ⚠️
 
@@ -46,13 +57,27 @@ This module is meant to be used in a web application where:
 
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
+use scc::hash_map::Entry;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+mod store;
+mod token;
+pub use store::{MemoryStore, RedisStore, SessionStore, SqlxStore};
+pub use token::TokenType;
+
+#[cfg(feature = "deterministic-sim")]
+pub mod sim;
+
+use token::TokenSigner;
+
 /// Session data structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionData {
@@ -67,30 +92,146 @@ pub struct SessionData {
 }
 
 /// Internal session storage with caching metadata
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct CachedSession {
+    pub(crate) data: SessionData,
+    pub(crate) cache_time: SystemTime,
+    pub(crate) last_db_sync: SystemTime,
+    pub(crate) version: u64, // Added but not used correctly - BUG!
+    /// Short session-token TTL, independent of `cache_time`/`cache_ttl`. Once
+    /// this elapses `get_session` fails and the caller must `refresh()`.
+    pub(crate) session_expires_at: SystemTime,
+}
+
+/// A long-lived refresh token record, keyed by its own id (distinct from the
+/// session id it points at). `rotated` is set the moment a refresh token is
+/// redeemed so a replay of the same token is detected and rejected.
 #[derive(Debug, Clone)]
-struct CachedSession {
-    data: SessionData,
-    cache_time: SystemTime,
-    last_db_sync: SystemTime,
-    version: u64, // Added but not used correctly - BUG!
+struct RefreshRecord {
+    session_id: String,
+    expires_at: SystemTime,
+    rotated: bool,
 }
 
-/// Global session storage using Lazy
-/// BUG: The storage uses RwLock but has logical race conditions
-static SESSION_STORE: Lazy<Arc<RwLock<HashMap<String, CachedSession>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(HashMap::new()))
-});
+/// One item in the downstream-cleanup resync queue: which session to clean
+/// up, how many attempts have already failed, and when the next attempt is
+/// due. Modeled on Garage's block resync queue.
+#[derive(Debug, Clone)]
+struct ResyncItem {
+    session_id: String,
+    attempts: u32,
+    next_retry: SystemTime,
+}
 
-/// Global cleanup queue that grows unbounded - BUG!
-static CLEANUP_QUEUE: Lazy<Arc<RwLock<Vec<String>>>> = Lazy::new(|| {
-    Arc::new(RwLock::new(Vec::new()))
-});
+/// How many times [`SessionManager::run_resync_worker`] retries a downstream
+/// cleanup before giving up and moving the item to [`DEAD_LETTER_QUEUE`].
+const RESYNC_MAX_ATTEMPTS: u32 = 8;
+
+/// Backoff for retry `n` is `RESYNC_BASE_BACKOFF * 2^n`, capped at
+/// `RESYNC_MAX_BACKOFF`.
+const RESYNC_BASE_BACKOFF: Duration = Duration::from_secs(1);
+const RESYNC_MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Bounded channel feeding [`SessionManager::run_resync_worker`]: producers
+/// (`delete_session`) only ever `try_send`, so a full channel sheds load
+/// instead of blocking the caller, and the channel itself can never grow
+/// past its capacity the way the old `Vec`-backed cleanup queue did.
+static RESYNC_QUEUE: Lazy<(flume::Sender<ResyncItem>, flume::Receiver<ResyncItem>)> =
+    Lazy::new(|| flume::bounded(10_000));
+
+/// Downstream cleanups that exhausted every retry in [`RESYNC_QUEUE`].
+/// Bounded and oldest-dropped, same as [`BoundedCleanupQueue`] - the session
+/// itself is already gone from the store either way; this is just a list for
+/// an operator to go investigate why the downstream side keeps rejecting it.
+static DEAD_LETTER_QUEUE: Lazy<Arc<RwLock<VecDeque<String>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(VecDeque::new())));
+
+const DEAD_LETTER_CAPACITY: usize = 1000;
+
+fn push_dead_letter(session_id: String) {
+    let mut queue = DEAD_LETTER_QUEUE.write();
+    queue.push_back(session_id);
+    if queue.len() > DEAD_LETTER_CAPACITY {
+        queue.pop_front();
+    }
+}
 
 /// Global statistics counter with race condition - BUG!
 static STATS: Lazy<Arc<RwLock<SessionStats>>> = Lazy::new(|| {
     Arc::new(RwLock::new(SessionStats::default()))
 });
 
+/// Global refresh-token table, keyed by refresh token id.
+///
+/// A lock-free `scc::HashMap` instead of a `parking_lot::RwLock`-guarded
+/// `HashMap`: every access below is a single synchronous call scoped to one
+/// key's bucket, so there's never a guard alive across an `.await` the way a
+/// `DashMap`/`RwLock` guard held into async code can deadlock (the class of
+/// bug that bit Tobira).
+static REFRESH_TOKENS: Lazy<scc::HashMap<String, RefreshRecord>> = Lazy::new(scc::HashMap::new);
+
+/// How long a session token is valid for before the caller must redeem its
+/// refresh token for a new one.
+const SESSION_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// How long a refresh token is valid for. Long-lived by design, since it's
+/// only ever exchanged for a session token, never sent with ordinary
+/// requests.
+const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Per-session access counters for [`SessionManager::new_consistent`], kept
+/// outside `CachedSession` so incrementing one never needs a clone-modify-save
+/// round trip through the store - it's a single `fetch_add`. Also a lock-free
+/// `scc::HashMap`, for the same reason as [`REFRESH_TOKENS`].
+static ACCESS_COUNTERS: Lazy<scc::HashMap<String, Arc<AtomicU64>>> = Lazy::new(scc::HashMap::new);
+
+fn access_counter(session_id: &str) -> Arc<AtomicU64> {
+    match ACCESS_COUNTERS.entry(session_id.to_string()) {
+        Entry::Occupied(entry) => entry.get().clone(),
+        Entry::Vacant(entry) => entry.insert_entry(Arc::new(AtomicU64::new(0))).get().clone(),
+    }
+}
+
+/// A bounded, deduplicated cleanup queue used by
+/// [`SessionManager::new_consistent`]: pushing an id already queued is a
+/// no-op, and once `capacity` is exceeded the oldest id is dropped, so this
+/// can never grow without limit the way an unbounded `Vec` would.
+struct BoundedCleanupQueue {
+    order: VecDeque<String>,
+    members: HashSet<String>,
+    capacity: usize,
+}
+
+impl BoundedCleanupQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            order: VecDeque::new(),
+            members: HashSet::new(),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, id: String) {
+        if !self.members.insert(id.clone()) {
+            return;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.members.remove(&oldest);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+}
+
+/// Cleanup queue used in `consistent` mode.
+static CONSISTENT_CLEANUP_QUEUE: Lazy<Arc<RwLock<BoundedCleanupQueue>>> =
+    Lazy::new(|| Arc::new(RwLock::new(BoundedCleanupQueue::new(1000))));
+
 #[derive(Debug, Clone, Default)]
 pub struct SessionStats {
     pub total_sessions: u64,
@@ -98,12 +239,50 @@ pub struct SessionStats {
     pub failed_cleanups: u64,
     pub cache_hits: u64,
     pub cache_misses: u64,
+    /// Number of times a `consistent`-mode `compare_and_swap` lost a race
+    /// and had to retry, across every call to `update_session`/
+    /// `increment_access`. A rising count is a direct signal of contention
+    /// on a given session, unlike the default mode's lost updates, which
+    /// leave no trace at all.
+    pub cas_retries: u64,
+    /// Sessions dropped by [`MemoryStore`]'s LRU eviction because the store
+    /// was at `max_sessions` capacity. Only ever incremented when a manager
+    /// is built with a capacity bound - see [`SessionManager::with_capacity`].
+    pub evictions: u64,
+    /// Sessions removed by [`SessionManager::run_cleanup`] for having gone
+    /// idle past `idle_timeout`, as opposed to an explicit `delete_session`.
+    pub expirations: u64,
 }
 
 /// Session Manager - the main API
+///
+/// Persistence is delegated entirely to a [`SessionStore`]; the manager only
+/// owns cache/TTL policy (`cache_ttl`, `cleanup_interval`). Swap in a
+/// `RedisStore` or `SqlxStore` to share sessions across multiple Actix
+/// workers or survive restarts — `MemoryStore` (the default) reproduces the
+/// previous single-process behavior.
 pub struct SessionManager {
+    store: Arc<dyn SessionStore>,
+    signer: Option<TokenSigner>,
     cache_ttl: Duration,
     cleanup_interval: Duration,
+    /// How long a session may go without activity before [`run_cleanup`](Self::run_cleanup)
+    /// reaps it and [`get_active_count`](Self::get_active_count) stops
+    /// counting it. Independent of `cleanup_interval`, which is just how
+    /// often the reaper wakes up to check.
+    idle_timeout: Duration,
+    /// Opt-in "fixed" mode (see [`new_consistent`](Self::new_consistent)):
+    /// `increment_access`/`update_session`/`delete_session` take the
+    /// lock-free/CAS-based paths documented on each method instead of the
+    /// demo's racy ones.
+    consistent: bool,
+    /// zstd compression level [`snapshot`](Self::snapshot) compresses at.
+    snapshot_level: i32,
+    /// How often [`run_snapshot_loop`](Self::run_snapshot_loop) writes a
+    /// fresh snapshot. `None` (the default) means periodic snapshotting is
+    /// off - a crash then loses everything since the last explicit
+    /// [`snapshot`](Self::snapshot)/[`shutdown`](Self::shutdown) call.
+    snapshot_interval: Option<Duration>,
 }
 
 impl Default for SessionManager {
@@ -114,15 +293,115 @@ impl Default for SessionManager {
 
 impl SessionManager {
     pub fn new() -> Self {
+        Self::with_store(Arc::new(MemoryStore::new()))
+    }
+
+    /// Build a manager backed by an arbitrary [`SessionStore`], e.g. a
+    /// `RedisStore` or `SqlxStore` for a multi-worker deployment. Tokens
+    /// returned to callers are unsigned but still type-tagged (see `token`
+    /// module) — forgery just isn't ruled out the way [`new_with_key`](Self::new_with_key) rules it out.
+    pub fn with_store(store: Arc<dyn SessionStore>) -> Self {
         Self {
+            store,
+            signer: None,
             cache_ttl: Duration::from_secs(60),
             cleanup_interval: Duration::from_secs(300),
+            idle_timeout: Duration::from_secs(30 * 60),
+            consistent: false,
+            snapshot_level: 3,
+            snapshot_interval: None,
+        }
+    }
+
+    /// Build a manager backed by a real Postgres database instead of the
+    /// in-process [`MemoryStore`], so sessions survive a restart. A thin
+    /// convenience wrapper over [`SqlxStore::connect`] + [`with_store`](Self::with_store) -
+    /// `SqlxStore` (and `RedisStore` alongside it) is this crate's `Backend`
+    /// abstraction: [`SessionStore`] already separates durable storage from
+    /// the cache/TTL policy this manager owns, so there's no separate
+    /// backend trait to plug in here.
+    pub async fn with_database(database_url: &str) -> Result<Self, String> {
+        let store = SqlxStore::connect(database_url).await?;
+        Ok(Self::with_store(Arc::new(store)))
+    }
+
+    /// Build a manager backed by a [`MemoryStore`] bounded to `max_sessions`
+    /// entries (LRU-evicting past that) and reaping sessions idle for longer
+    /// than `idle_timeout`. Use this instead of [`new`](Self::new) whenever
+    /// the process shouldn't grow its session cache without bound.
+    pub fn with_capacity(max_sessions: usize, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            ..Self::with_store(Arc::new(MemoryStore::with_max_sessions(max_sessions)))
+        }
+    }
+
+    /// Build a manager with every documented race condition actually fixed:
+    /// access counts are tracked with an [`AtomicU64`] instead of a racy
+    /// read-modify-write, `update_session` retries against the store's
+    /// [`SessionStore::compare_and_swap`] instead of blindly overwriting,
+    /// and deleted ids go onto [`CONSISTENT_CLEANUP_QUEUE`], a bounded,
+    /// deduplicated queue, instead of an unbounded one.
+    /// `get_session`/`create_session`/`refresh` are unaffected - the bugs
+    /// they could have are elsewhere.
+    pub fn new_consistent() -> Self {
+        Self {
+            consistent: true,
+            ..Self::new()
+        }
+    }
+
+    /// Build a manager that signs every session id it hands out with
+    /// HMAC-SHA256 over `secret`, so a forged or guessed id is rejected by
+    /// [`verify_token`](Self::verify_token) before it ever reaches the store.
+    pub fn new_with_key(secret: &[u8]) -> Self {
+        Self {
+            signer: Some(TokenSigner::new(secret)),
+            ..Self::new()
+        }
+    }
+
+    /// Encode a raw id as a token of the given type, signing it when the
+    /// manager has a key and falling back to the plain type-tagged encoding
+    /// otherwise.
+    fn encode(&self, kind: TokenType, id: &str) -> String {
+        match &self.signer {
+            Some(signer) => signer.sign(kind, id),
+            None => token::encode_plain(kind, id),
+        }
+    }
+
+    /// Decode a caller-supplied token, verifying its signature (if any) and
+    /// rejecting it outright if it isn't the expected [`TokenType`] — a
+    /// refresh token can never be used where a session token is expected.
+    fn decode(&self, token: &str, expected: TokenType) -> Result<String, String> {
+        let (kind, id) = match &self.signer {
+            Some(signer) => signer.verify(token)?,
+            None => token::decode_plain(token)?,
+        };
+
+        if kind != expected {
+            return Err(format!("expected a {:?} token", expected));
         }
+
+        Ok(id)
+    }
+
+    /// Verify a caller-supplied session token and return the raw session id
+    /// it encodes. Fails if the token is malformed, forged (when the
+    /// manager was built with a key), or is actually a refresh token.
+    pub fn verify_token(&self, token: &str) -> Result<String, String> {
+        self.decode(token, TokenType::Session)
     }
 
     /// Create a new session
     /// BUG: The session counter update is not atomic with the insert
-    pub fn create_session(&self, user_id: String, username: String) -> Result<String, String> {
+    ///
+    /// Returns a `(session_token, refresh_token)` pair: the session token is
+    /// short-lived ([`SESSION_TOKEN_TTL`]) and accepted by [`get_session`](Self::get_session)
+    /// and friends, while the refresh token is long-lived and only accepted
+    /// by [`refresh`](Self::refresh) to mint a new pair.
+    pub async fn create_session(&self, user_id: String, username: String) -> Result<(String, String), String> {
         let session_id = Uuid::new_v4().to_string();
         let now = SystemTime::now();
 
@@ -142,15 +421,13 @@ impl SessionManager {
             cache_time: now,
             last_db_sync: now,
             version: 1,
+            session_expires_at: now + SESSION_TOKEN_TTL,
         };
 
         // BUG: Non-atomic read-modify-write pattern
         // Another thread could modify stats between these operations
-        {
-            let mut store = SESSION_STORE.write();
-            store.insert(session_id.clone(), cached);
-        }
-        
+        self.store.save(&session_id, cached).await?;
+
         // BUG: Stats update is separate from session insert
         // If this panics or is interrupted, stats will be inconsistent
         {
@@ -159,233 +436,415 @@ impl SessionManager {
             stats.active_sessions += 1;
         }
 
-        Ok(session_id)
+        let refresh_id = Uuid::new_v4().to_string();
+        let _ = REFRESH_TOKENS.insert(
+            refresh_id.clone(),
+            RefreshRecord {
+                session_id: session_id.clone(),
+                expires_at: now + REFRESH_TOKEN_TTL,
+                rotated: false,
+            },
+        );
+
+        Ok((
+            self.encode(TokenType::Session, &session_id),
+            self.encode(TokenType::Refresh, &refresh_id),
+        ))
     }
 
-    /// Get session data with cache
-    /// BUG: The cache validity check has a race condition
-    pub fn get_session(&self, session_id: &str) -> Result<SessionData, String> {
+    /// Redeem a refresh token for a fresh `(session_token, refresh_token)`
+    /// pair belonging to the same session.
+    ///
+    /// The old refresh token is marked `rotated` the instant it's redeemed,
+    /// so replaying it afterwards — e.g. an attacker who intercepted it in
+    /// transit — is rejected even though the underlying session is still
+    /// alive.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<(String, String), String> {
+        let refresh_id = self.decode(refresh_token, TokenType::Refresh)?;
         let now = SystemTime::now();
-        
-        // BUG: Two-phase lock pattern - read then potentially write
-        // Another thread could invalidate our assumptions between locks
-        let needs_refresh = {
-            let store = SESSION_STORE.read();
-            match store.get(session_id) {
-                Some(cached) => {
-                    // BUG: Cache TTL check is racy
-                    if let Ok(elapsed) = now.duration_since(cached.cache_time) {
-                        if elapsed < self.cache_ttl {
-                            // Cache hit - but we drop the lock here!
-                            drop(store);
-                            
-                            // BUG: Update stats without holding session lock
-                            let mut stats = STATS.write();
-                            stats.cache_hits += 1;
-                            drop(stats);
-                            
-                            // BUG: Re-acquire lock - session might have changed!
-                            let store = SESSION_STORE.read();
-                            return Ok(store.get(session_id)
-                                .ok_or_else(|| "Session disappeared".to_string())?
-                                .data.clone());
-                        }
-                        true // Needs refresh
-                    } else {
-                        true
-                    }
+
+        let session_id = match REFRESH_TOKENS.entry(refresh_id) {
+            Entry::Vacant(_) => return Err("unknown refresh token".to_string()),
+            Entry::Occupied(mut entry) => {
+                let record = entry.get_mut();
+
+                if record.rotated {
+                    return Err("refresh token already used".to_string());
                 }
-                None => return Err("Session not found".to_string()),
+                if now.duration_since(record.expires_at).is_ok() {
+                    return Err("refresh token expired".to_string());
+                }
+
+                record.rotated = true;
+                record.session_id.clone()
             }
         };
 
-        if needs_refresh {
-            // BUG: Update stats outside of transaction
-            let mut stats = STATS.write();
-            stats.cache_misses += 1;
-            drop(stats);
-            
-            // Simulate database fetch with delay
-            self.refresh_from_database(session_id)?;
-            
-            // BUG: Re-read after refresh - could get stale data if another thread
-            // also did a refresh with older data that completed after ours
-            let store = SESSION_STORE.read();
-            Ok(store.get(session_id)
-                .ok_or_else(|| "Session not found after refresh".to_string())?
-                .data.clone())
-        } else {
-            Err("Unreachable code reached".to_string())
+        let mut cached = self
+            .store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| "session not found".to_string())?;
+        cached.session_expires_at = now + SESSION_TOKEN_TTL;
+        self.store.save(&session_id, cached).await?;
+
+        let new_refresh_id = Uuid::new_v4().to_string();
+        let _ = REFRESH_TOKENS.insert(
+            new_refresh_id.clone(),
+            RefreshRecord {
+                session_id: session_id.clone(),
+                expires_at: now + REFRESH_TOKEN_TTL,
+                rotated: false,
+            },
+        );
+
+        Ok((
+            self.encode(TokenType::Session, &session_id),
+            self.encode(TokenType::Refresh, &new_refresh_id),
+        ))
+    }
+
+    /// Get session data with cache
+    /// BUG: The cache validity check has a race condition
+    pub async fn get_session(&self, token: &str) -> Result<SessionData, String> {
+        let session_id = self.verify_token(token)?;
+        let now = SystemTime::now();
+
+        let cached = self
+            .store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| "Session not found".to_string())?;
+
+        if now.duration_since(cached.session_expires_at).is_ok() {
+            return Err("session token expired; call refresh()".to_string());
         }
+
+        // BUG: Cache TTL check is racy - nothing stops another task from
+        // refreshing or deleting the session between this check and the use
+        // of its result below.
+        //
+        // `cache_ttl == Duration::ZERO` means "caching disabled": checked
+        // explicitly rather than relying on `elapsed < Duration::ZERO` never
+        // being true, since that's the same fragile zero-duration edge case
+        // that's bitten timing code elsewhere.
+        if !self.cache_ttl.is_zero() {
+            if let Ok(elapsed) = now.duration_since(cached.cache_time) {
+                if elapsed < self.cache_ttl {
+                    let mut stats = STATS.write();
+                    stats.cache_hits += 1;
+                    drop(stats);
+
+                    return Ok(cached.data);
+                }
+            }
+        }
+
+        // BUG: Update stats outside of transaction
+        let mut stats = STATS.write();
+        stats.cache_misses += 1;
+        drop(stats);
+
+        // Simulate database fetch with delay
+        self.refresh_from_database(&session_id).await?;
+
+        // BUG: Re-read after refresh - could get stale data if another task
+        // also did a refresh with older data that completed after ours
+        self.store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| "Session not found after refresh".to_string())
+            .map(|cached| cached.data)
     }
 
     /// Update session data
     /// BUG: Lost update problem - classic read-modify-write race
-    pub fn update_session(&self, session_id: &str, metadata_key: String, metadata_value: String) -> Result<(), String> {
-        // BUG: Read-modify-write without proper isolation
-        let mut session_data = {
-            let store = SESSION_STORE.read();
-            let cached = store.get(session_id)
-                .ok_or_else(|| "Session not found".to_string())?;
-            cached.data.clone() // Clone the data
-        }; // Lock is released here!
-        
-        // BUG: Between releasing the read lock and acquiring the write lock,
-        // another thread could have modified the session. Our update will
-        // overwrite their changes!
-        session_data.metadata.insert(metadata_key, metadata_value);
-        session_data.access_count += 1;
-        session_data.last_activity = SystemTime::now();
-        
-        // Acquire write lock and update
-        {
-            let mut store = SESSION_STORE.write();
-            if let Some(cached) = store.get_mut(session_id) {
-                // BUG: We're overwriting with our locally modified copy,
-                // potentially losing updates made by other threads
-                cached.data = session_data;
-                // BUG: Version is incremented but never actually checked!
-                cached.version += 1;
+    ///
+    /// In `consistent` mode, retries against [`SessionStore::compare_and_swap`]
+    /// instead: load, compute the new state, and only commit if nobody else's
+    /// version beat us to it; otherwise re-read and try again, up to 16 times,
+    /// recording each retry in [`SessionStats::cas_retries`] so contention on
+    /// a session is visible instead of just a lost update with no trace.
+    pub async fn update_session(&self, token: &str, metadata_key: String, metadata_value: String) -> Result<(), String> {
+        let session_id = self.verify_token(token)?;
+
+        if self.consistent {
+            const MAX_ATTEMPTS: usize = 16;
+            for _ in 0..MAX_ATTEMPTS {
+                let mut cached = self
+                    .store
+                    .load(&session_id)
+                    .await?
+                    .ok_or_else(|| "Session not found".to_string())?;
+                let expected_version = cached.version;
+
+                cached.data.metadata.insert(metadata_key.clone(), metadata_value.clone());
+                cached.data.access_count += 1;
+                cached.data.last_activity = SystemTime::now();
+                cached.version = expected_version + 1;
+
+                if self.store.compare_and_swap(&session_id, expected_version, cached).await? {
+                    return Ok(());
+                }
+                // Someone else's write landed first; retry from a fresh read.
+                STATS.write().cas_retries += 1;
             }
+            return Err("Conflict: too many concurrent writers to session".to_string());
         }
 
-        Ok(())
+        // BUG: Read-modify-write without proper isolation
+        let mut cached = self
+            .store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| "Session not found".to_string())?;
+
+        // BUG: Between loading and saving, another task could have modified
+        // the session. Our update will overwrite their changes!
+        cached.data.metadata.insert(metadata_key, metadata_value);
+        cached.data.access_count += 1;
+        cached.data.last_activity = SystemTime::now();
+        // BUG: Version is incremented but never actually checked!
+        cached.version += 1;
+
+        self.store.save(&session_id, cached).await
     }
 
     /// Increment access count
     /// BUG: Another read-modify-write race condition
-    pub fn increment_access(&self, session_id: &str) -> Result<u64, String> {
-        let new_count = {
-            let store = SESSION_STORE.read();
-            let cached = store.get(session_id)
-                .ok_or_else(|| "Session not found".to_string())?;
-            cached.data.access_count + 1 // Read current value
-        }; // Lock released!
-        
-        // BUG: Another thread could increment between our read and write
-        {
-            let mut store = SESSION_STORE.write();
-            if let Some(cached) = store.get_mut(session_id) {
-                // BUG: Overwriting with our calculated value, losing concurrent increments
-                cached.data.access_count = new_count;
+    ///
+    /// In `consistent` mode, the count lives in an [`AtomicU64`] keyed by
+    /// session id (see [`access_counter`]) so concurrent increments use
+    /// `fetch_add` and none are lost; the stored snapshot is then updated
+    /// with that value so `get_session` still reflects it.
+    pub async fn increment_access(&self, token: &str) -> Result<u64, String> {
+        let session_id = self.verify_token(token)?;
+
+        if self.consistent {
+            if self.store.load(&session_id).await?.is_none() {
+                return Err("Session not found".to_string());
+            }
+
+            let new_count = access_counter(&session_id).fetch_add(1, Ordering::SeqCst) + 1;
+
+            const MAX_ATTEMPTS: usize = 16;
+            for _ in 0..MAX_ATTEMPTS {
+                let Some(mut cached) = self.store.load(&session_id).await? else {
+                    return Err("Session not found".to_string());
+                };
+                let expected_version = cached.version;
+                // The atomic counter is the source of truth; never move the
+                // stored snapshot backwards if our save loses a race to a
+                // newer increment that already landed.
+                cached.data.access_count = cached.data.access_count.max(new_count);
                 cached.data.last_activity = SystemTime::now();
+                cached.version = expected_version + 1;
+
+                if self.store.compare_and_swap(&session_id, expected_version, cached).await? {
+                    break;
+                }
+                STATS.write().cas_retries += 1;
             }
+
+            return Ok(new_count);
         }
-        
+
+        let mut cached = self
+            .store
+            .load(&session_id)
+            .await?
+            .ok_or_else(|| "Session not found".to_string())?;
+
+        // BUG: Another task could increment between our load and save
+        let new_count = cached.data.access_count + 1;
+        cached.data.access_count = new_count;
+        cached.data.last_activity = SystemTime::now();
+
+        self.store.save(&session_id, cached).await?;
         Ok(new_count)
     }
 
     /// Delete a session
     /// BUG: Phantom session problem - inconsistent state across data structures
-    pub fn delete_session(&self, session_id: &str) -> Result<(), String> {
-        // BUG: Multi-step deletion with gaps between operations
-        
-        // Step 1: Remove from main store
-        let was_present = {
-            let mut store = SESSION_STORE.write();
-            store.remove(session_id).is_some()
-        }; // Lock released!
-        
+    ///
+    /// `consistent` mode closes the stats gap by updating `STATS` immediately
+    /// after the store mutation (same as the buggy path - there's no store
+    /// primitive to make the two a single transaction) but files the id onto
+    /// [`CONSISTENT_CLEANUP_QUEUE`], which dedups and is capacity-bounded,
+    /// instead of [`RESYNC_QUEUE`] - the two don't need the same dedup/retry
+    /// machinery since `CONSISTENT_CLEANUP_QUEUE` isn't drained by anything
+    /// that can itself fail.
+    pub async fn delete_session(&self, token: &str) -> Result<(), String> {
+        let session_id = self.verify_token(token)?;
+
+        let was_present = self.store.load(&session_id).await?.is_some();
+        self.store.delete(&session_id).await?;
+
         if !was_present {
             return Err("Session not found".to_string());
         }
-        
+
+        if self.consistent {
+            {
+                let mut stats = STATS.write();
+                stats.active_sessions = stats.active_sessions.saturating_sub(1);
+            }
+            CONSISTENT_CLEANUP_QUEUE.write().push(session_id.clone());
+            let _ = ACCESS_COUNTERS.remove(&session_id);
+            return Ok(());
+        }
+
+        // BUG: Multi-step deletion with gaps between operations
+
         // BUG: Gap here - session is removed but stats not updated
         // If someone calls get_stats() now, they'll see incorrect active count
-        
+
         // Step 2: Update statistics
         {
             let mut stats = STATS.write();
             stats.active_sessions = stats.active_sessions.saturating_sub(1);
         }
-        
-        // BUG: Gap here - if cleanup fails, the session is gone but cleanup queue grows
-        
-        // Step 3: Add to cleanup queue (to simulate database cleanup)
-        {
-            let mut queue = CLEANUP_QUEUE.write();
-            queue.push(session_id.to_string());
-        }
-        
+
+        // BUG: Gap here - if cleanup fails, the session is gone but the
+        // downstream cleanup (below) might never have been queued
+
+        // Step 3: Queue a downstream cleanup job (e.g. invalidating cached
+        // CDN entries tied to this session) for `run_resync_worker` to pick
+        // up. `try_send` never blocks the caller; if the channel is somehow
+        // full we drop the item rather than backing up `delete_session`
+        // itself - the session is already gone from the store regardless.
+        let _ = RESYNC_QUEUE.0.try_send(ResyncItem {
+            session_id: session_id.to_string(),
+            attempts: 0,
+            next_retry: SystemTime::now(),
+        });
+
         Ok(())
     }
 
     /// Simulate refreshing session from database
     /// BUG: Can overwrite newer data with older data due to improper timestamp checking
-    fn refresh_from_database(&self, session_id: &str) -> Result<(), String> {
+    async fn refresh_from_database(&self, session_id: &str) -> Result<(), String> {
         // Simulate database delay
-        std::thread::sleep(Duration::from_millis(10));
-        
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
         let now = SystemTime::now();
-        
-        // BUG: Read current version without holding lock during "database fetch"
-        let current_version = {
-            let store = SESSION_STORE.read();
-            store.get(session_id)
-                .map(|cached| cached.version)
-                .unwrap_or(0)
-        }; // Lock released during "database fetch"!
-        
-        // Simulate fetching from database (in reality, this is just reading and re-writing)
-        // BUG: Another thread could have updated with newer data while we were "fetching"
-        
-        let fetched_data = {
-            let store = SESSION_STORE.read();
-            store.get(session_id)
-                .map(|cached| cached.data.clone())
-                .ok_or_else(|| "Session not found".to_string())?
-        };
-        
-        // BUG: Update without checking if data is actually newer
-        {
-            let mut store = SESSION_STORE.write();
-            if let Some(cached) = store.get_mut(session_id) {
-                // BUG: We compare version but use the wrong logic
-                // Should reject if current version > fetched version, but we don't
-                if cached.version >= current_version {
-                    // BUG: This condition is backwards - we update even when we shouldn't
-                    cached.data = fetched_data;
-                    cached.cache_time = now;
-                    cached.last_db_sync = now;
-                }
-            }
+
+        // BUG: Read current version without holding a lock during the
+        // "database fetch" - another task could race us here.
+        let current_version = self
+            .store
+            .load(session_id)
+            .await?
+            .map(|cached| cached.version)
+            .unwrap_or(0);
+
+        // Simulate fetching from database (in reality, this is just reading and re-saving)
+        // BUG: Another task could have updated with newer data while we were "fetching"
+        let mut cached = self
+            .store
+            .load(session_id)
+            .await?
+            .ok_or_else(|| "Session not found".to_string())?;
+
+        // BUG: We compare version but use the wrong logic
+        // Should reject if current version > fetched version, but we don't
+        if cached.version >= current_version {
+            // BUG: This condition is backwards - we update even when we shouldn't
+            cached.cache_time = now;
+            cached.last_db_sync = now;
+            self.store.save(session_id, cached).await?;
         }
-        
+
         Ok(())
     }
 
     /// Run cleanup task
-    /// BUG: The cleanup queue grows unbounded because failed items are never removed
+    ///
+    /// Walks the store's own idea of what's expired (`list_expired`) instead
+    /// of draining the unbounded in-memory deletion queue, so cleanup no
+    /// longer depends on every caller of `delete_session` having remembered
+    /// to enqueue anything.
     pub async fn run_cleanup(&self) {
         loop {
             tokio::time::sleep(self.cleanup_interval).await;
-            
-            // BUG: Process cleanup queue but don't remove failed items
-            let items_to_clean = {
-                let queue = CLEANUP_QUEUE.read();
-                queue.clone() // Clone entire queue
+
+            let expired = match self.store.list_expired(self.idle_timeout).await {
+                Ok(ids) => ids,
+                Err(_) => continue,
             };
-            
-            for session_id in items_to_clean {
-                // Simulate cleanup operation that might fail
-                let cleanup_success = session_id.len() % 2 == 0; // Arbitrary condition
-                
-                if cleanup_success {
-                    // Remove from queue only if successful
-                    let mut queue = CLEANUP_QUEUE.write();
-                    if let Some(pos) = queue.iter().position(|id| id == &session_id) {
-                        queue.remove(pos);
-                    }
-                } else {
-                    // BUG: Failed cleanups stay in queue forever!
-                    // Queue grows unbounded
+
+            for session_id in expired {
+                if self.store.delete(&session_id).await.is_ok() {
                     let mut stats = STATS.write();
-                    stats.failed_cleanups += 1;
+                    stats.active_sessions = stats.active_sessions.saturating_sub(1);
+                    stats.expirations += 1;
                 }
             }
+
+            // Downstream cleanup for expired (as opposed to explicitly
+            // deleted) sessions is handled the same way `delete_session`
+            // handles it - queue it and let `run_resync_worker` retry it.
+            for session_id in self.store.list_expired(self.idle_timeout).await.unwrap_or_default() {
+                let _ = RESYNC_QUEUE.0.try_send(ResyncItem {
+                    session_id,
+                    attempts: 0,
+                    next_retry: SystemTime::now(),
+                });
+            }
         }
     }
 
+    /// Placeholder for whatever downstream system actually needs to know
+    /// about a deleted session (e.g. invalidating CDN-cached responses keyed
+    /// on it). Always succeeds in this demo - swap in the real call when
+    /// there is one; [`run_resync_worker`](Self::run_resync_worker)'s
+    /// retry/backoff/dead-letter handling is exercised by the `is_err` path
+    /// whenever that real call starts failing.
+    async fn perform_downstream_cleanup(&self, _session_id: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Drains [`RESYNC_QUEUE`], running [`perform_downstream_cleanup`](Self::perform_downstream_cleanup)
+    /// for each item as its `next_retry` comes due. A failure is rescheduled
+    /// with exponential backoff (`RESYNC_BASE_BACKOFF * 2^attempts`, capped
+    /// at `RESYNC_MAX_BACKOFF`); after `RESYNC_MAX_ATTEMPTS` failures the
+    /// item moves to [`DEAD_LETTER_QUEUE`] and `failed_cleanups` is
+    /// incremented. Meant to run as a spawned background task alongside
+    /// [`run_cleanup`](Self::run_cleanup).
+    pub async fn run_resync_worker(&self) {
+        while let Ok(item) = RESYNC_QUEUE.1.recv_async().await {
+            if let Ok(wait) = item.next_retry.duration_since(SystemTime::now()) {
+                tokio::time::sleep(wait).await;
+            }
+
+            if self.perform_downstream_cleanup(&item.session_id).await.is_ok() {
+                continue;
+            }
+
+            let attempts = item.attempts + 1;
+            if attempts >= RESYNC_MAX_ATTEMPTS {
+                STATS.write().failed_cleanups += 1;
+                push_dead_letter(item.session_id);
+                continue;
+            }
+
+            let backoff = (RESYNC_BASE_BACKOFF * 2u32.pow(attempts)).min(RESYNC_MAX_BACKOFF);
+            let _ = RESYNC_QUEUE.0.try_send(ResyncItem {
+                session_id: item.session_id,
+                attempts,
+                next_retry: SystemTime::now() + backoff,
+            });
+        }
+    }
+
+    /// Number of items permanently given up on after exhausting every resync
+    /// retry. A non-zero count means the downstream cleanup target (e.g. the
+    /// CDN cache) is rejecting requests, not that any session data was lost.
+    pub fn get_dead_letter_queue_size(&self) -> usize {
+        DEAD_LETTER_QUEUE.read().len()
+    }
+
     /// Get statistics
     /// BUG: Statistics are inconsistent due to race conditions in other methods
     pub fn get_stats(&self) -> SessionStats {
@@ -393,17 +852,143 @@ impl SessionManager {
         stats.clone()
     }
 
+    /// Get statistics with `active_sessions` replaced by a live count from
+    /// the store, rather than the `STATS` counter `update_session`/
+    /// `increment_access`/`delete_session` can drift out of sync with. Only
+    /// meaningful in `consistent` mode - the other fields still come from
+    /// the same shared `STATS` struct.
+    pub async fn get_consistent_stats(&self) -> SessionStats {
+        let mut stats = STATS.read().clone();
+        stats.active_sessions = self.store.count().await.unwrap_or(stats.active_sessions);
+        stats
+    }
+
     /// Get active session count
     /// BUG: This count may not match actual sessions due to race conditions
-    pub fn get_active_count(&self) -> usize {
-        let store = SESSION_STORE.read();
-        store.len()
+    ///
+    /// Subtracts sessions `list_expired(idle_timeout)` would reap, so an
+    /// idle session stops being counted immediately rather than waiting for
+    /// the next `run_cleanup` pass to physically remove it.
+    pub async fn get_active_count(&self) -> usize {
+        let total = self.store.count().await.unwrap_or(0);
+        let expired = self.store.list_expired(self.idle_timeout).await.map(|ids| ids.len()).unwrap_or(0);
+        total.saturating_sub(expired)
     }
 
-    /// Get cleanup queue size (for debugging the memory leak)
+    /// Get cleanup queue size. In `consistent` mode this is
+    /// [`CONSISTENT_CLEANUP_QUEUE`]'s length; otherwise it's how many
+    /// downstream cleanups are sitting in [`RESYNC_QUEUE`] waiting on their
+    /// next retry.
     pub fn get_cleanup_queue_size(&self) -> usize {
-        let queue = CLEANUP_QUEUE.read();
-        queue.len()
+        if self.consistent {
+            return CONSISTENT_CLEANUP_QUEUE.read().len();
+        }
+        RESYNC_QUEUE.1.len()
+    }
+
+    /// Build a manager backed by a [`MemoryStore`] warmed from a snapshot
+    /// previously written by [`snapshot`](Self::snapshot)/[`shutdown`](Self::shutdown).
+    /// A missing file is not an error - it just means "start cold", the same
+    /// as [`new`](Self::new) - but a present, corrupt one is, since that
+    /// usually means the process crashed mid-write and silently losing every
+    /// session would be worse than failing startup.
+    pub async fn with_persistence(path: impl AsRef<Path>) -> Result<Self, String> {
+        let manager = Self::new();
+        manager.restore_snapshot(path).await?;
+        Ok(manager)
+    }
+
+    /// Load a snapshot file written by [`snapshot`](Self::snapshot) and warm
+    /// `self.store` with every session in it, skipping ones that have
+    /// already gone past `idle_timeout` while the process was down.
+    async fn restore_snapshot(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let compressed = std::fs::read(path).map_err(|e| format!("failed to read snapshot: {e}"))?;
+        let json = zstd::decode_all(&compressed[..]).map_err(|e| format!("failed to decompress snapshot: {e}"))?;
+        let sessions: Vec<CachedSession> =
+            serde_json::from_slice(&json).map_err(|e| format!("failed to parse snapshot: {e}"))?;
+
+        let now = SystemTime::now();
+        for cached in sessions {
+            let idle_since = now
+                .duration_since(cached.data.last_activity)
+                .unwrap_or(Duration::ZERO);
+            if idle_since >= self.idle_timeout {
+                continue;
+            }
+            let session_id = cached.data.session_id.clone();
+            self.store.save(&session_id, cached).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write every session currently in the store to `path`, serialized as
+    /// JSON and zstd-compressed at `snapshot_level`. Entries already past
+    /// `idle_timeout` are dropped rather than carried forward - there's no
+    /// point restoring a session [`run_cleanup`](Self::run_cleanup) would
+    /// reap on the next pass anyway.
+    pub async fn snapshot(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let now = SystemTime::now();
+        let sessions: Vec<CachedSession> = self
+            .store
+            .all()
+            .await?
+            .into_iter()
+            .filter(|cached| {
+                now.duration_since(cached.data.last_activity)
+                    .map(|idle| idle < self.idle_timeout)
+                    .unwrap_or(true)
+            })
+            .collect();
+
+        let json = serde_json::to_vec(&sessions).map_err(|e| format!("failed to serialize snapshot: {e}"))?;
+        let compressed =
+            zstd::encode_all(&json[..], self.snapshot_level).map_err(|e| format!("failed to compress snapshot: {e}"))?;
+
+        let path = path.as_ref();
+        let mut file = std::fs::File::create(path).map_err(|e| format!("failed to create snapshot file: {e}"))?;
+        file.write_all(&compressed).map_err(|e| format!("failed to write snapshot: {e}"))?;
+
+        Ok(())
+    }
+
+    /// Enable periodic snapshotting every `interval`; call before handing the
+    /// manager to [`run_snapshot_loop`](Self::run_snapshot_loop). Mirrors
+    /// `cleanup_interval`'s role for [`run_cleanup`](Self::run_cleanup) - a
+    /// plain setter rather than a constructor arg, since it's orthogonal to
+    /// which store/TTLs the manager was built with.
+    pub fn with_snapshot_interval(mut self, interval: Duration) -> Self {
+        self.snapshot_interval = Some(interval);
+        self
+    }
+
+    /// Periodically write a snapshot to `path` every `snapshot_interval`, for
+    /// as long as that's set (see [`with_snapshot_interval`](Self::with_snapshot_interval)).
+    /// Returns immediately if no interval was configured - there's nothing
+    /// to loop on. Intended to run alongside [`run_cleanup`](Self::run_cleanup)
+    /// as a spawned background task.
+    pub async fn run_snapshot_loop(&self, path: impl AsRef<Path>) {
+        let Some(interval) = self.snapshot_interval else {
+            return;
+        };
+
+        loop {
+            tokio::time::sleep(interval).await;
+            let _ = self.snapshot(&path).await;
+        }
+    }
+
+    /// Write a final snapshot to `path`. Call this on graceful shutdown
+    /// (e.g. a `SIGTERM` handler) so the next [`with_persistence`](Self::with_persistence)
+    /// restores exactly what was active, rather than relying solely on
+    /// whatever [`run_snapshot_loop`](Self::run_snapshot_loop) last wrote.
+    pub async fn shutdown(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        self.snapshot(path).await
     }
 }
 
@@ -411,18 +996,18 @@ impl SessionManager {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_create_session() {
+    #[tokio::test]
+    async fn test_create_session() {
         let manager = SessionManager::new();
-        let result = manager.create_session("user123".to_string(), "alice".to_string());
+        let result = manager.create_session("user123".to_string(), "alice".to_string()).await;
         assert!(result.is_ok());
     }
 
-    #[test]
-    fn test_get_session() {
+    #[tokio::test]
+    async fn test_get_session() {
         let manager = SessionManager::new();
-        let session_id = manager.create_session("user456".to_string(), "bob".to_string()).unwrap();
-        let result = manager.get_session(&session_id);
+        let (session_id, _refresh_id) = manager.create_session("user456".to_string(), "bob".to_string()).await.unwrap();
+        let result = manager.get_session(&session_id).await;
         assert!(result.is_ok());
     }
 
@@ -430,9 +1015,9 @@ mod tests {
     async fn test_concurrent_updates_show_bug() {
         // This test demonstrates the lost update bug
         let manager = Arc::new(SessionManager::new());
-        let session_id = manager.create_session("user789".to_string(), "charlie".to_string()).unwrap();
-        
-        // Spawn multiple threads that update the same session
+        let (session_id, _refresh_id) = manager.create_session("user789".to_string(), "charlie".to_string()).await.unwrap();
+
+        // Spawn multiple tasks that update the same session
         let mut handles = vec![];
         for i in 0..10 {
             let manager_clone = manager.clone();
@@ -441,21 +1026,21 @@ mod tests {
                 for j in 0..10 {
                     let key = format!("key_{}", i);
                     let value = format!("value_{}_{}", i, j);
-                    let _ = manager_clone.update_session(&session_id_clone, key, value);
+                    let _ = manager_clone.update_session(&session_id_clone, key, value).await;
                 }
             });
             handles.push(handle);
         }
-        
-        // Wait for all threads
+
+        // Wait for all tasks
         for handle in handles {
             handle.await.unwrap();
         }
-        
+
         // BUG: Due to race conditions, some updates will be lost
         // The metadata map should have 10 keys (key_0 through key_9)
         // but might have fewer due to lost updates
-        let session = manager.get_session(&session_id).unwrap();
+        let session = manager.get_session(&session_id).await.unwrap();
         println!("Metadata keys after concurrent updates: {}", session.metadata.len());
         // This might fail: assert_eq!(session.metadata.len(), 10);
     }
@@ -464,32 +1049,192 @@ mod tests {
     async fn test_concurrent_increment_shows_bug() {
         // This test demonstrates the lost increment bug
         let manager = Arc::new(SessionManager::new());
-        let session_id = manager.create_session("user999".to_string(), "dave".to_string()).unwrap();
-        
-        // Spawn multiple threads that increment access count
+        let (session_id, _refresh_id) = manager.create_session("user999".to_string(), "dave".to_string()).await.unwrap();
+
+        // Spawn multiple tasks that increment access count
         let mut handles = vec![];
         for _ in 0..20 {
             let manager_clone = manager.clone();
             let session_id_clone = session_id.clone();
             let handle = tokio::spawn(async move {
                 for _ in 0..50 {
-                    let _ = manager_clone.increment_access(&session_id_clone);
+                    let _ = manager_clone.increment_access(&session_id_clone).await;
                 }
             });
             handles.push(handle);
         }
-        
-        // Wait for all threads
+
+        // Wait for all tasks
         for handle in handles {
             handle.await.unwrap();
         }
-        
+
         // BUG: Due to race conditions, the count will be less than expected
         // Expected: 20 threads * 50 increments = 1000
         // Actual: Much less due to lost updates
-        let session = manager.get_session(&session_id).unwrap();
+        let session = manager.get_session(&session_id).await.unwrap();
         println!("Access count after 1000 concurrent increments: {}", session.access_count);
         println!("Expected: 1000, Got: {}", session.access_count);
         // This will likely fail: assert_eq!(session.access_count, 1000);
     }
+
+    #[tokio::test]
+    async fn test_memory_store_round_trip() {
+        let store = MemoryStore::new();
+        assert_eq!(store.count().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_signed_session_round_trip() {
+        let manager = SessionManager::new_with_key(b"test-secret");
+        let (token, _refresh_token) = manager
+            .create_session("user1".to_string(), "erin".to_string())
+            .await
+            .unwrap();
+
+        // The token is not a bare UUID any more.
+        assert!(token.contains('.'));
+
+        let session = manager.get_session(&token).await.unwrap();
+        assert_eq!(session.username, "erin");
+    }
+
+    #[tokio::test]
+    async fn test_forged_session_token_is_rejected() {
+        let manager = SessionManager::new_with_key(b"test-secret");
+        let (token, _refresh_token) = manager
+            .create_session("user2".to_string(), "frank".to_string())
+            .await
+            .unwrap();
+
+        let mut forged = token.clone();
+        forged.push('z');
+
+        assert!(manager.get_session(&forged).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_mints_new_session() {
+        let manager = SessionManager::new_with_key(b"test-secret");
+        let (session_token, refresh_token) = manager
+            .create_session("user3".to_string(), "grace".to_string())
+            .await
+            .unwrap();
+
+        let (new_session_token, new_refresh_token) = manager.refresh(&refresh_token).await.unwrap();
+        assert_ne!(session_token, new_session_token);
+        assert_ne!(refresh_token, new_refresh_token);
+
+        let session = manager.get_session(&new_session_token).await.unwrap();
+        assert_eq!(session.username, "grace");
+    }
+
+    #[tokio::test]
+    async fn test_replayed_refresh_token_is_rejected() {
+        let manager = SessionManager::new_with_key(b"test-secret");
+        let (_session_token, refresh_token) = manager
+            .create_session("user4".to_string(), "heidi".to_string())
+            .await
+            .unwrap();
+
+        assert!(manager.refresh(&refresh_token).await.is_ok());
+        // Replaying the same refresh token a second time must fail, even
+        // though it hasn't expired.
+        assert!(manager.refresh(&refresh_token).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_cannot_be_used_as_session_token() {
+        let manager = SessionManager::new_with_key(b"test-secret");
+        let (_session_token, refresh_token) = manager
+            .create_session("user5".to_string(), "ivan".to_string())
+            .await
+            .unwrap();
+
+        assert!(manager.get_session(&refresh_token).await.is_err());
+    }
+}
+
+/// Deterministic regression coverage for the concurrency bugs documented at
+/// the top of this module - see the `sim` module for how determinism is
+/// achieved. Gated behind `deterministic-sim` rather than plain `test` since
+/// it depends on `start_paused` time control interacting predictably with
+/// every spawned task, which is a stronger requirement than the
+/// best-effort `test_concurrent_*` tests above make.
+#[cfg(all(test, feature = "deterministic-sim"))]
+mod sim_tests {
+    use super::*;
+    use crate::sim::seeded_jitter_millis;
+
+    async fn run_concurrent_updates(manager: Arc<SessionManager>, session_id: String, seed: u64) {
+        let mut handles = vec![];
+        for i in 0..10u64 {
+            let manager = manager.clone();
+            let session_id = session_id.clone();
+            handles.push(tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(seeded_jitter_millis(seed, i))).await;
+                let _ = manager
+                    .update_session(&session_id, format!("key_{i}"), format!("value_{i}"))
+                    .await;
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+    }
+
+    /// For a fixed seed, the lost-update window always loses the same
+    /// updates: two runs of the same seed land on the same metadata count,
+    /// and that count is strictly less than the 10 updates sent - unlike
+    /// `test_concurrent_updates_show_bug`, which only sometimes catches the
+    /// bug depending on however the OS scheduler happened to interleave
+    /// threads that run.
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn seed_42_reproduces_the_same_lost_update_window_every_run() {
+        async fn run_once(seed: u64) -> usize {
+            let manager = Arc::new(SessionManager::new());
+            let (session_id, _refresh_id) = manager
+                .create_session("user-sim".to_string(), "alice".to_string())
+                .await
+                .unwrap();
+            run_concurrent_updates(manager.clone(), session_id.clone(), seed).await;
+            manager.get_session(&session_id).await.unwrap().metadata.len()
+        }
+
+        let first = run_once(42).await;
+        let second = run_once(42).await;
+        assert_eq!(first, second, "same seed must reproduce the same interleaving every time");
+        assert!(first < 10, "seed 42 was chosen to land inside the lost-update window");
+    }
+
+    /// The CAS-based `consistent` path has no lost-update window for any
+    /// interleaving, so unlike the test above this doesn't need to special-
+    /// case a particular seed: 20 tasks each doing 50 increments always
+    /// lands on exactly 1000, deterministic seed or not.
+    #[tokio::test(flavor = "current_thread", start_paused = true)]
+    async fn consistent_mode_gets_exactly_1000_increments() {
+        let manager = Arc::new(SessionManager::new_consistent());
+        let (session_id, _refresh_id) = manager
+            .create_session("user-sim".to_string(), "dave".to_string())
+            .await
+            .unwrap();
+
+        let mut handles = vec![];
+        for i in 0..20u64 {
+            let manager = manager.clone();
+            let session_id = session_id.clone();
+            handles.push(tokio::spawn(async move {
+                for j in 0..50u64 {
+                    tokio::time::sleep(Duration::from_millis(seeded_jitter_millis(7, i * 50 + j))).await;
+                    let _ = manager.increment_access(&session_id).await;
+                }
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let session = manager.get_session(&session_id).await.unwrap();
+        assert_eq!(session.access_count, 1000);
+    }
 }
@@ -0,0 +1,23 @@
+//! Deterministic concurrency test harness (feature `deterministic-sim`).
+//!
+//! `tokio::time::pause` already gives this crate an injectable virtual clock
+//! for free: under a `current_thread` runtime with paused time, virtual time
+//! only advances once every task is parked on a timer, so tasks are polled in
+//! a fixed, environment-independent order for any given sequence of `sleep`
+//! calls. What that doesn't fix on its own is *which* task's `sleep` lands at
+//! which virtual instant - [`seeded_jitter_millis`] derives that from a seed
+//! with a small splitmix64 PRNG (the same scheme `Module 12` uses for its
+//! hydration fix), so a fixed seed always produces the same task interleaving
+//! and a test built on it is reproducible instead of "might fail".
+
+/// Reproducible per-task jitter, in milliseconds, derived from `seed` and
+/// `task_index`. Tests sleep for this long before doing their racy work so a
+/// given `(seed, task count)` always interleaves the same way under paused
+/// time.
+pub fn seeded_jitter_millis(seed: u64, task_index: u64) -> u64 {
+    let mut state = seed ^ task_index.wrapping_mul(0x9E3779B97F4A7C15);
+    state ^= state >> 12;
+    state ^= state << 25;
+    state ^= state >> 27;
+    (state.wrapping_mul(0x2545_F491_4F6C_DD1D) % 50) + 1
+}
@@ -0,0 +1,451 @@
+//! Pluggable persistence backends for session state.
+//!
+//! `SessionManager` no longer talks to a single hard-coded in-process map.
+//! Instead it delegates all reads/writes to a [`SessionStore`] implementation,
+//! mirroring the store abstraction used by `tower-sessions`. This is what lets
+//! `MemoryStore` reproduce today's single-node behavior while `RedisStore` and
+//! `SqlxStore` let sessions survive restarts and be shared across multiple
+//! Actix workers.
+
+use async_trait::async_trait;
+use linked_hash_map::LinkedHashMap;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::CachedSession;
+
+/// Persistence contract that `SessionManager` is generic over.
+///
+/// Implementations only need to get bytes in and out reliably; all cache/TTL
+/// bookkeeping stays in `CachedSession` itself so a store doesn't need to know
+/// about the manager's policies. `compare_and_swap` is the one operation that
+/// does need backend-specific atomicity (a lock, `WATCH`/`MULTI`, or a
+/// conditional `UPDATE`) so `SessionManager`'s retry loops have something
+/// real to retry against instead of two plain, racy `load`/`save` calls.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Load a session by id, if it exists.
+    async fn load(&self, id: &str) -> Result<Option<CachedSession>, String>;
+
+    /// Insert or overwrite a session.
+    async fn save(&self, id: &str, session: CachedSession) -> Result<(), String>;
+
+    /// Remove a session. Not finding one is not an error.
+    async fn delete(&self, id: &str) -> Result<(), String>;
+
+    /// Ids of every session whose `last_activity` is older than `idle_timeout`.
+    async fn list_expired(&self, idle_timeout: Duration) -> Result<Vec<String>, String>;
+
+    /// Total number of sessions currently stored.
+    async fn count(&self) -> Result<usize, String>;
+
+    /// Save `session` only if the record currently stored under `id` still
+    /// has `expected_version` (optimistic concurrency control via
+    /// [`CachedSession::version`]). Returns `Ok(true)` if the write landed,
+    /// `Ok(false)` if a concurrent writer already moved the version on and
+    /// the caller should re-read and retry.
+    async fn compare_and_swap(
+        &self,
+        id: &str,
+        expected_version: u64,
+        session: CachedSession,
+    ) -> Result<bool, String>;
+
+    /// Every session currently stored, for `SessionManager::snapshot` to
+    /// serialize wholesale. Not used on any hot path, so stores that don't
+    /// keep an in-memory index (e.g. `RedisStore`) are free to implement it
+    /// with a scan.
+    async fn all(&self) -> Result<Vec<CachedSession>, String>;
+}
+
+/// In-process store backed by a `LinkedHashMap`, so it tracks access order
+/// and can enforce a bounded size the same way Gotham's in-memory session
+/// backend does: the least-recently-used entry is evicted whenever an
+/// insert would push the map past `max_sessions`. Reproduces the previous
+/// global `Lazy<RwLock<HashMap<..>>>` behavior when `max_sessions` is `None`
+/// (the default), just owned by the manager instead of living at `'static`
+/// scope.
+#[derive(Clone)]
+pub struct MemoryStore {
+    inner: Arc<RwLock<LinkedHashMap<String, CachedSession>>>,
+    max_sessions: Option<usize>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(LinkedHashMap::new())),
+            max_sessions: None,
+        }
+    }
+
+    /// Same as [`new`](Self::new), but evicting the least-recently-used
+    /// session whenever an insert would grow the store past `max_sessions`.
+    /// Each eviction is counted in [`crate::SessionStats::evictions`].
+    pub fn with_max_sessions(max_sessions: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(LinkedHashMap::new())),
+            max_sessions: Some(max_sessions),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<CachedSession>, String> {
+        // `get_refresh` (not a plain `get`) so a read also counts as a use,
+        // moving the entry to the back of the eviction order - the "U" in
+        // LRU.
+        Ok(self.inner.write().get_refresh(id).cloned())
+    }
+
+    async fn save(&self, id: &str, session: CachedSession) -> Result<(), String> {
+        let mut inner = self.inner.write();
+        inner.insert(id.to_string(), session);
+
+        if let Some(max_sessions) = self.max_sessions {
+            while inner.len() > max_sessions {
+                if inner.pop_front().is_some() {
+                    crate::STATS.write().evictions += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), String> {
+        self.inner.write().remove(id);
+        Ok(())
+    }
+
+    async fn list_expired(&self, idle_timeout: Duration) -> Result<Vec<String>, String> {
+        let now = SystemTime::now();
+        let expired = self
+            .inner
+            .read()
+            .iter()
+            .filter(|(_, cached)| {
+                now.duration_since(cached.data.last_activity)
+                    .map(|elapsed| elapsed >= idle_timeout)
+                    .unwrap_or(false)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        Ok(expired)
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        Ok(self.inner.read().len())
+    }
+
+    async fn compare_and_swap(
+        &self,
+        id: &str,
+        expected_version: u64,
+        session: CachedSession,
+    ) -> Result<bool, String> {
+        let mut inner = self.inner.write();
+        if inner.get(id).map(|cached| cached.version) != Some(expected_version) {
+            return Ok(false);
+        }
+        inner.insert(id.to_string(), session);
+        Ok(true)
+    }
+
+    async fn all(&self) -> Result<Vec<CachedSession>, String> {
+        Ok(self.inner.read().iter().map(|(_, cached)| cached.clone()).collect())
+    }
+}
+
+/// Redis-backed store so sessions survive restarts and can be shared across
+/// multiple Actix workers or hosts. Sessions are stored as JSON under a
+/// `session:{id}` key.
+pub struct RedisStore {
+    client: redis::Client,
+    key_prefix: String,
+}
+
+impl RedisStore {
+    pub fn new(redis_url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(redis_url).map_err(|e| e.to_string())?;
+        Ok(Self {
+            client,
+            key_prefix: "session:".to_string(),
+        })
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.key_prefix, id)
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisStore {
+    async fn load(&self, id: &str) -> Result<Option<CachedSession>, String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let raw: Option<String> = conn.get(self.key(id)).await.map_err(|e| e.to_string())?;
+        raw.map(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    async fn save(&self, id: &str, session: CachedSession) -> Result<(), String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(&session).map_err(|e| e.to_string())?;
+        conn.set(self.key(id), json)
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        conn.del(self.key(id))
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())
+    }
+
+    async fn list_expired(&self, idle_timeout: Duration) -> Result<Vec<String>, String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let keys: Vec<String> = conn
+            .keys(format!("{}*", self.key_prefix))
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())?;
+        let now = SystemTime::now();
+        let mut expired = Vec::new();
+        for key in keys {
+            let raw: Option<String> = conn.get(&key).await.map_err(|e: redis::RedisError| e.to_string())?;
+            if let Some(json) = raw {
+                if let Ok(cached) = serde_json::from_str::<CachedSession>(&json) {
+                    let is_expired = now
+                        .duration_since(cached.data.last_activity)
+                        .map(|elapsed| elapsed >= idle_timeout)
+                        .unwrap_or(false);
+                    if is_expired {
+                        expired.push(key.trim_start_matches(&self.key_prefix).to_string());
+                    }
+                }
+            }
+        }
+        Ok(expired)
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let keys: Vec<String> = conn
+            .keys(format!("{}*", self.key_prefix))
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())?;
+        Ok(keys.len())
+    }
+
+    async fn compare_and_swap(
+        &self,
+        id: &str,
+        expected_version: u64,
+        session: CachedSession,
+    ) -> Result<bool, String> {
+        use redis::AsyncCommands;
+        // `WATCH` is connection-scoped: on the multiplexed connection this
+        // store otherwise uses, unrelated commands from other concurrent
+        // callers share the same physical connection and can interleave
+        // with this transaction, silently breaking the optimistic-locking
+        // guarantee. A dedicated connection keeps `WATCH`/`MULTI`/`EXEC`
+        // isolated to this one call.
+        let mut conn = self
+            .client
+            .get_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let key = self.key(id);
+
+        conn.watch(&key).await.map_err(|e: redis::RedisError| e.to_string())?;
+
+        let raw: Option<String> = conn.get(&key).await.map_err(|e: redis::RedisError| e.to_string())?;
+        let current_version = raw
+            .as_deref()
+            .and_then(|json| serde_json::from_str::<CachedSession>(json).ok())
+            .map(|cached| cached.version);
+
+        if current_version != Some(expected_version) {
+            conn.unwatch().await.map_err(|e: redis::RedisError| e.to_string())?;
+            return Ok(false);
+        }
+
+        let json = serde_json::to_string(&session).map_err(|e| e.to_string())?;
+        let mut pipe = redis::pipe();
+        pipe.atomic().set(&key, json);
+        let result: Option<()> = pipe
+            .query_async(&mut conn)
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())?;
+        Ok(result.is_some())
+    }
+
+    async fn all(&self) -> Result<Vec<CachedSession>, String> {
+        use redis::AsyncCommands;
+        let mut conn = self
+            .client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string())?;
+        let keys: Vec<String> = conn
+            .keys(format!("{}*", self.key_prefix))
+            .await
+            .map_err(|e: redis::RedisError| e.to_string())?;
+        let mut sessions = Vec::with_capacity(keys.len());
+        for key in keys {
+            let raw: Option<String> = conn.get(&key).await.map_err(|e: redis::RedisError| e.to_string())?;
+            if let Some(json) = raw {
+                if let Ok(cached) = serde_json::from_str::<CachedSession>(&json) {
+                    sessions.push(cached);
+                }
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+/// Postgres-backed store via `sqlx`, for deployments that already run a
+/// relational database and would rather not add Redis as a dependency.
+pub struct SqlxStore {
+    pool: sqlx::PgPool,
+}
+
+impl SqlxStore {
+    pub async fn connect(database_url: &str) -> Result<Self, String> {
+        let pool = sqlx::PgPool::connect(database_url)
+            .await
+            .map_err(|e| e.to_string())?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                id TEXT PRIMARY KEY,
+                data JSONB NOT NULL,
+                last_activity TIMESTAMPTZ NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl SessionStore for SqlxStore {
+    async fn load(&self, id: &str) -> Result<Option<CachedSession>, String> {
+        let row: Option<(serde_json::Value,)> =
+            sqlx::query_as("SELECT data FROM sessions WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        row.map(|(json,)| serde_json::from_value(json).map_err(|e| e.to_string()))
+            .transpose()
+    }
+
+    async fn save(&self, id: &str, session: CachedSession) -> Result<(), String> {
+        let json = serde_json::to_value(&session).map_err(|e| e.to_string())?;
+        sqlx::query(
+            "INSERT INTO sessions (id, data, last_activity) VALUES ($1, $2, $3)
+             ON CONFLICT (id) DO UPDATE SET data = $2, last_activity = $3",
+        )
+        .bind(id)
+        .bind(json)
+        .bind(chrono::DateTime::<chrono::Utc>::from(session.data.last_activity))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM sessions WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn list_expired(&self, idle_timeout: Duration) -> Result<Vec<String>, String> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(idle_timeout).unwrap_or_default();
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT id FROM sessions WHERE last_activity < $1")
+                .bind(cutoff)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    async fn count(&self) -> Result<usize, String> {
+        let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM sessions")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(row.0 as usize)
+    }
+
+    async fn compare_and_swap(
+        &self,
+        id: &str,
+        expected_version: u64,
+        session: CachedSession,
+    ) -> Result<bool, String> {
+        let json = serde_json::to_value(&session).map_err(|e| e.to_string())?;
+        let result = sqlx::query(
+            "UPDATE sessions SET data = $2, last_activity = $3
+             WHERE id = $1 AND (data->>'version')::bigint = $4",
+        )
+        .bind(id)
+        .bind(json)
+        .bind(chrono::DateTime::<chrono::Utc>::from(session.data.last_activity))
+        .bind(expected_version as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn all(&self) -> Result<Vec<CachedSession>, String> {
+        let rows: Vec<(serde_json::Value,)> = sqlx::query_as("SELECT data FROM sessions")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        rows.into_iter()
+            .map(|(json,)| serde_json::from_value(json).map_err(|e| e.to_string()))
+            .collect()
+    }
+}